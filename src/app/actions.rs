@@ -1,29 +1,28 @@
 use super::{App, AppMode};
-use crate::app::task_utils;
+use crate::app::merge::Side;
+use crate::app::task::TaskId;
+use crate::app::{parse, task_utils};
 use crate::event::AppEvent;
 use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use uuid::Uuid;
 
 /// Handles the key events and updates the state of [`App`].
 pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> color_eyre::Result<()> {
+    app.status_message = None;
     match app.mode {
         AppMode::Normal => {
+            if handle_bulk_keys(key_event, app) {
+                return Ok(());
+            }
             if key_event == app.config.keys.quit {
                 app.events.send(AppEvent::Quit);
             } else if key_event == app.config.keys.add_task {
                 app.mode = AppMode::Editing;
-            } else if key_event == app.config.keys.delete_task {
-                app.events.send(AppEvent::DeleteTask);
             } else if key_event == app.config.keys.edit_task {
-                if let Some(selected) = app.task_list_state.selected() {
-                    let tasks_to_display = app.get_tasks_to_display();
-                    if let Some(selected_task_info) = tasks_to_display.get(selected) {
-                        let task_path = selected_task_info.1.clone();
-                        let mut task_ref = &app.tasks[task_path[0]];
-                        for &index in task_path.iter().skip(1) {
-                            task_ref = &task_ref.subtasks[index];
-                        }
-                        app.input = task_ref.name.clone();
-                        app.mode = AppMode::EditingTask { path: task_path };
+                if let Some(id) = app.selected_id() {
+                    if let Some(task_ref) = app.task(id) {
+                        app.input = parse::format_task_input(task_ref);
+                        app.mode = AppMode::EditingTask { id };
                     }
                 }
             } else if key_event == app.config.keys.toggle_expand {
@@ -33,11 +32,116 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> color_eyre::Resu
             } else if key_event == app.config.keys.select_next {
                 select_next_task(app);
             } else if key_event == app.config.keys.deselect {
-                app.task_list_state.select(None);
+                app.select_index(None);
+            } else if key_event == app.config.keys.toggle_mark {
+                toggle_mark(app);
+            } else if key_event == app.config.keys.visual_mode {
+                if let Some(selected) = app.task_list_state.selected() {
+                    app.mode = AppMode::Visual { anchor: selected };
+                }
+            } else if key_event == app.config.keys.clear_marks {
+                app.marked.clear();
+            } else if key_event == app.config.keys.cycle_sort {
+                app.cycle_sort();
+            } else if key_event == app.config.keys.sort_siblings {
+                if let Some(id) = app.selected_id() {
+                    app.events.send(AppEvent::SortSiblings { id });
+                }
+            } else if key_event == app.config.keys.cycle_group {
+                app.cycle_group();
+            } else if key_event == app.config.keys.zoom_in {
+                app.zoom_in();
+            } else if key_event == app.config.keys.zoom_out {
+                app.zoom_out();
+            } else if key_event == app.config.keys.expand_subtree {
+                set_subtree_expanded(app, true);
+            } else if key_event == app.config.keys.collapse_subtree {
+                set_subtree_expanded(app, false);
+            } else if key_event == app.config.keys.expand_all {
+                set_all_expanded(app, true);
+            } else if key_event == app.config.keys.collapse_all {
+                set_all_expanded(app, false);
+            } else if key_event == app.config.keys.expand_to_depth {
+                app.input.clear();
+                app.mode = AppMode::ExpandDepth;
+            } else if key_event == app.config.keys.toggle_details {
+                app.show_details = !app.show_details;
+            } else if key_event == app.config.keys.block_task {
+                request_block_task(app);
+            } else if key_event == app.config.keys.unblock_task {
+                if let Some(id) = app.selected_id() {
+                    app.events.send(AppEvent::UnblockTask { id });
+                }
+            } else if key_event == app.config.keys.toggle_timer {
+                if let Some(id) = app.selected_id() {
+                    app.events.send(AppEvent::ToggleTimer { id });
+                }
+            } else if key_event == app.config.keys.toggle_pomodoro {
+                if let Some(id) = app
+                    .selected_id()
+                    .or(app.pomodoro.as_ref().map(|pomodoro| pomodoro.task))
+                {
+                    app.events.send(AppEvent::TogglePomodoro { id });
+                }
+            } else if key_event == app.config.keys.archive_completed {
+                let older_than_days = app.config.archive.older_than_days;
+                let event = AppEvent::ArchiveCompleted { older_than_days };
+                if app.config.confirm.bulk_actions {
+                    app.mode = AppMode::Confirm {
+                        message: format!(
+                            "Archive every task completed over {} day(s) ago?",
+                            older_than_days
+                        ),
+                        on_confirm: event,
+                    };
+                } else {
+                    app.events.send(event);
+                }
+            } else if key_event == app.config.keys.show_archive {
+                app.mode = AppMode::Archive;
+                let has_rows = !app.archive.is_empty();
+                app.archive_list_state.select(has_rows.then_some(0));
+            } else if key_event == app.config.keys.show_trash {
+                app.purge_expired_trash();
+                app.mode = AppMode::Trash;
+                let has_rows = !app.trash.is_empty();
+                app.trash_list_state.select(has_rows.then_some(0));
+            } else if key_event == app.config.keys.show_backups {
+                app.open_backup_browser();
+            } else if key_event == app.config.keys.show_history {
+                app.open_history();
+            } else if key_event == app.config.keys.sync_calendar {
+                app.sync_calendar();
+            } else if key_event == app.config.keys.next_actionable {
+                app.actionable_only = !app.actionable_only;
+                app.clamp_selection();
+            }
+        }
+        AppMode::Visual { .. } => {
+            if handle_bulk_keys(key_event, app) {
+                return Ok(());
+            }
+            if key_event == app.config.keys.select_previous {
+                select_previous_task(app);
+            } else if key_event == app.config.keys.select_next {
+                select_next_task(app);
+            } else if key_event == app.config.keys.visual_mode {
+                app.commit_visual_range();
+            } else if key_event == app.config.keys.clear_marks {
+                app.mode = AppMode::Normal;
             }
         }
         AppMode::Editing => match key_event.code {
-            KeyCode::Enter => app.events.send(AppEvent::AddTask),
+            KeyCode::Enter => {
+                let parent = app.selected_id().or(app.zoom);
+                let input = app.input.drain(..).collect();
+                app.events.send(AppEvent::AddTask {
+                    id: Uuid::new_v4(),
+                    parent,
+                    input,
+                });
+                app.mode = AppMode::Normal;
+            }
             KeyCode::Char(c) => {
                 app.input.push(c);
             }
@@ -49,8 +153,12 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> color_eyre::Resu
             }
             _ => {}
         },
-        AppMode::EditingTask { .. } => match key_event.code {
-            KeyCode::Enter => app.events.send(AppEvent::UpdateTask),
+        AppMode::EditingTask { id } => match key_event.code {
+            KeyCode::Enter => {
+                let input = app.input.drain(..).collect();
+                app.events.send(AppEvent::UpdateTask { id, input });
+                app.mode = AppMode::Normal;
+            }
             KeyCode::Char(c) => app.input.push(c),
             KeyCode::Backspace => {
                 app.input.pop();
@@ -58,25 +166,360 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> color_eyre::Resu
             KeyCode::Esc => app.mode = AppMode::Normal,
             _ => {}
         },
+        AppMode::Tagging => match key_event.code {
+            KeyCode::Enter => {
+                let ids = app.target_ids();
+                let tag = app.input.drain(..).collect();
+                request_bulk_action(app, "Tag", AppEvent::TagTasks { ids, tag });
+            }
+            KeyCode::Char(c) => app.input.push(c),
+            KeyCode::Backspace => {
+                app.input.pop();
+            }
+            KeyCode::Esc => app.mode = AppMode::Normal,
+            _ => {}
+        },
+        AppMode::ExpandDepth => match key_event.code {
+            KeyCode::Enter => {
+                let depth = app.input.drain(..).collect::<String>().trim().parse();
+                if let Ok(depth) = depth {
+                    expand_to_depth(app, depth);
+                }
+                app.mode = AppMode::Normal;
+            }
+            KeyCode::Char(c) if c.is_ascii_digit() => app.input.push(c),
+            KeyCode::Backspace => {
+                app.input.pop();
+            }
+            KeyCode::Esc => app.mode = AppMode::Normal,
+            _ => {}
+        },
+        AppMode::Archive => {
+            let keys = &app.config.keys;
+            if key_event == keys.select_next {
+                app.archive_list_state.select_next();
+            } else if key_event == keys.select_previous {
+                app.archive_list_state.select_previous();
+            } else if key_event == keys.restore_task {
+                let selected = app.archive_list_state.selected();
+                if let Some(entry) = selected.and_then(|index| app.archive.get(index)) {
+                    let id = entry.task.id;
+                    app.events.send(AppEvent::RestoreArchived { id });
+                }
+            } else if key_event == keys.show_archive
+                || key_event == keys.clear_marks
+                || key_event == keys.quit
+            {
+                app.mode = AppMode::Normal;
+            }
+        }
+        AppMode::Trash => {
+            let keys = &app.config.keys;
+            let selected_id = app
+                .trash_list_state
+                .selected()
+                .and_then(|index| app.trash.get(index))
+                .map(|entry| entry.task.id);
+            if key_event == keys.select_next {
+                app.trash_list_state.select_next();
+            } else if key_event == keys.select_previous {
+                app.trash_list_state.select_previous();
+            } else if key_event == keys.restore_task {
+                if let Some(id) = selected_id {
+                    app.events.send(AppEvent::RestoreTrashed { id });
+                }
+            } else if key_event == keys.delete_task {
+                if let Some(id) = selected_id {
+                    app.mode = AppMode::Confirm {
+                        message: "Permanently delete this task? This cannot be undone.".to_string(),
+                        on_confirm: AppEvent::PurgeTrashed { ids: vec![id] },
+                    };
+                }
+            } else if key_event == keys.show_trash
+                || key_event == keys.clear_marks
+                || key_event == keys.quit
+            {
+                app.mode = AppMode::Normal;
+            }
+        }
+        AppMode::Backups => {
+            let keys = &app.config.keys;
+            if key_event == keys.select_next {
+                app.select_backup(1);
+            } else if key_event == keys.select_previous {
+                app.select_backup(-1);
+            } else if key_event == keys.restore_task {
+                let selected = app.backup_list_state.selected();
+                if let Some(selected_backup) = selected.and_then(|index| app.backups.get(index)) {
+                    app.mode = AppMode::Confirm {
+                        message: format!(
+                            "Replace all tasks with the backup from {}?",
+                            selected_backup.created.format("%Y-%m-%d %H:%M")
+                        ),
+                        on_confirm: AppEvent::RestoreBackup {
+                            path: selected_backup.path.clone(),
+                        },
+                    };
+                }
+            } else if key_event == keys.show_backups
+                || key_event == keys.clear_marks
+                || key_event == keys.quit
+            {
+                app.mode = AppMode::Normal;
+            }
+        }
+        AppMode::History => {
+            let keys = &app.config.keys;
+            if key_event == keys.select_next {
+                app.select_commit(1);
+            } else if key_event == keys.select_previous {
+                app.select_commit(-1);
+            } else if key_event == keys.restore_task {
+                let selected = app.history_list_state.selected();
+                if let Some(commit) = selected.and_then(|index| app.history.get(index)) {
+                    app.mode = AppMode::Confirm {
+                        message: format!(
+                            "Roll all tasks back to commit {} ({})?",
+                            commit.short_hash(),
+                            commit.subject
+                        ),
+                        on_confirm: AppEvent::RollBack {
+                            hash: commit.hash.clone(),
+                        },
+                    };
+                }
+            } else if key_event == keys.show_history
+                || key_event == keys.clear_marks
+                || key_event == keys.quit
+            {
+                app.mode = AppMode::Normal;
+            }
+        }
+        AppMode::Conflicts => {
+            let keys = &app.config.keys;
+            let selected = app.conflict_list_state.selected();
+            let selected_id = selected
+                .and_then(|index| app.conflicts.get(index))
+                .map(|conflict| conflict.id);
+            if key_event == keys.select_next {
+                app.select_conflict(1);
+            } else if key_event == keys.select_previous {
+                app.select_conflict(-1);
+            } else if key_event == keys.keep_ours || key_event == keys.keep_theirs {
+                let side = if key_event == keys.keep_ours {
+                    Side::Ours
+                } else {
+                    Side::Theirs
+                };
+                if let Some(id) = selected_id {
+                    app.events.send(AppEvent::ResolveConflict { id, side });
+                }
+            } else if key_event == keys.clear_marks || key_event == keys.quit {
+                app.dismiss_conflicts();
+            }
+        }
+        AppMode::Confirm { .. } => match key_event.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                if let AppMode::Confirm { on_confirm, .. } =
+                    std::mem::replace(&mut app.mode, AppMode::Normal)
+                {
+                    app.mode = mode_after_confirm(&on_confirm);
+                    app.events.send(on_confirm);
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                if let AppMode::Confirm { on_confirm, .. } = &app.mode {
+                    app.mode = mode_after_confirm(on_confirm);
+                }
+            }
+            _ => {}
+        },
     }
     Ok(())
 }
 
+/// The mode to go back to once a confirmation prompt for `event` is answered.
+fn mode_after_confirm(event: &AppEvent) -> AppMode {
+    match event {
+        AppEvent::PurgeTrashed { .. } => AppMode::Trash,
+        _ => AppMode::Normal,
+    }
+}
+
+/// Handles the keys that act on the marked tasks, shared by normal and visual mode. Returns
+/// whether the key was consumed.
+fn handle_bulk_keys(key_event: KeyEvent, app: &mut App) -> bool {
+    let keys = &app.config.keys;
+    if key_event == keys.delete_task {
+        app.commit_visual_range();
+        if app.marked.is_empty() {
+            request_delete_task(app);
+        } else {
+            let ids = app.target_ids();
+            request_bulk_action(app, "Delete", AppEvent::DeleteTasks { ids });
+        }
+    } else if key_event == keys.toggle_complete {
+        request_toggle_complete(app);
+    } else if key_event == keys.move_marked {
+        app.commit_visual_range();
+        if app.marked.is_empty() {
+            app.status_message = Some("Mark tasks before moving them".to_string());
+        } else {
+            let ids = app.target_ids();
+            let parent = app.selected_id().or(app.zoom);
+            request_bulk_action(app, "Move", AppEvent::MoveTasks { ids, parent });
+        }
+    } else if key_event == keys.tag_task {
+        app.commit_visual_range();
+        app.input.clear();
+        app.mode = AppMode::Tagging;
+    } else if key_event == keys.archive_tasks {
+        let ids = app.target_ids();
+        request_bulk_action(app, "Archive", AppEvent::ArchiveTasks { ids });
+    } else if key_event == keys.export_tasks {
+        let ids = app.target_ids();
+        request_bulk_action(app, "Export", AppEvent::ExportTasks { ids });
+    } else if key_event == keys.yank_task {
+        app.commit_visual_range();
+        let ids = app.target_ids();
+        app.events.send(AppEvent::YankTasks { ids });
+    } else if key_event == keys.cut_task {
+        let ids = app.target_ids();
+        request_bulk_action(app, "Cut", AppEvent::CutTasks { ids });
+    } else if key_event == keys.paste_sibling || key_event == keys.paste_child {
+        let as_child = key_event == keys.paste_child;
+        app.commit_visual_range();
+        // With nothing selected, paste at the top of the view
+        let (target, as_child) = match app.selected_id() {
+            Some(id) => (Some(id), as_child),
+            None => (app.zoom, true),
+        };
+        app.events.send(AppEvent::PasteTasks { target, as_child });
+    } else {
+        return false;
+    }
+    true
+}
+
+/// Sends `event`, asking first if it would act on a set of marked tasks.
+fn request_bulk_action(app: &mut App, verb: &str, event: AppEvent) {
+    app.commit_visual_range();
+    let marked_count = app.marked.len();
+    if marked_count > 0 && app.config.confirm.bulk_actions {
+        app.mode = AppMode::Confirm {
+            message: format!("{} {} marked task(s)?", verb, marked_count),
+            on_confirm: event,
+        };
+    } else {
+        app.mode = AppMode::Normal;
+        app.events.send(event);
+    }
+}
+
+/// Completes the target tasks, asking first whether to force it if any of them are blocked.
+fn request_toggle_complete(app: &mut App) {
+    app.commit_visual_range();
+    let ids = app.target_ids();
+    let tasks: Vec<_> = ids.iter().filter_map(|&id| app.task(id)).collect();
+    let completing = tasks.iter().any(|task| !task.completed);
+    let blocked_count = tasks.iter().filter(|task| app.is_blocked(task)).count();
+    if completing && blocked_count > 0 {
+        app.mode = AppMode::Confirm {
+            message: format!(
+                "{} task(s) are still blocked. Complete anyway?",
+                blocked_count
+            ),
+            on_confirm: AppEvent::ToggleComplete { ids, force: true },
+        };
+    } else {
+        let event = AppEvent::ToggleComplete { ids, force: false };
+        request_bulk_action(app, "Toggle completion of", event);
+    }
+}
+
+/// Makes the selected task wait on the marked tasks.
+fn request_block_task(app: &mut App) {
+    let Some(id) = app.selected_id() else {
+        return;
+    };
+    let blockers: Vec<TaskId> = app
+        .marked
+        .iter()
+        .copied()
+        .filter(|&marked| marked != id)
+        .collect();
+    if blockers.is_empty() {
+        app.status_message = Some("Mark the tasks that block the selected one first".to_string());
+    } else {
+        app.events.send(AppEvent::BlockTask { id, blockers });
+    }
+}
+
+/// Marks or unmarks the selected task, then moves down so runs of tasks are quick to mark.
+fn toggle_mark(app: &mut App) {
+    if let Some(id) = app.selected_id() {
+        if !app.marked.remove(&id) {
+            app.marked.insert(id);
+        }
+    }
+    select_next_task(app);
+}
+
+/// Deletes the selected task, asking first if it would take a subtree with it.
+fn request_delete_task(app: &mut App) {
+    if let Some(id) = app.selected_id() {
+        if let Some(task_ref) = app.task(id) {
+            let subtask_count = task_ref.descendant_count();
+            let on_confirm = AppEvent::DeleteTasks { ids: vec![id] };
+            if subtask_count > 0 && app.config.confirm.delete_task {
+                app.mode = AppMode::Confirm {
+                    message: format!(
+                        "Delete '{}' and its {} subtask(s)?",
+                        task_ref.name, subtask_count
+                    ),
+                    on_confirm,
+                };
+            } else {
+                app.events.send(on_confirm);
+            }
+        }
+    }
+}
+
 fn toggle_expand_task(app: &mut App) {
-    if let Some(selected_index) = app.task_list_state.selected() {
-        let tasks_to_display = app.get_tasks_to_display();
-        if let Some(selected_task_info) = tasks_to_display.get(selected_index) {
-            let task_path = &selected_task_info.1;
-            if let Some(task_ref) = task_utils::get_task_mut(&mut app.tasks, task_path) {
-                // Only toggle if the task is already expanded or has subtasks
-                if task_ref.expanded || !task_ref.subtasks.is_empty() {
-                    task_ref.expanded = !task_ref.expanded;
-                }
+    if let Some(id) = app.selected_id() {
+        if let Some(task_ref) = app.task_mut(id) {
+            // Only toggle if the task is already expanded or has subtasks
+            if task_ref.expanded || !task_ref.subtasks.is_empty() {
+                task_ref.expanded = !task_ref.expanded;
             }
         }
     }
 }
 
+/// Expands or collapses the selected task together with everything below it.
+fn set_subtree_expanded(app: &mut App, expanded: bool) {
+    if let Some(id) = app.selected_id() {
+        if let Some(task_ref) = app.task_mut(id) {
+            task_ref.set_expanded_recursive(expanded);
+        }
+    }
+}
+
+/// Expands or collapses every task in the tree.
+fn set_all_expanded(app: &mut App, expanded: bool) {
+    for task in &mut app.tasks {
+        task.set_expanded_recursive(expanded);
+    }
+    app.sync_selection();
+}
+
+/// Shows `depth` levels below the top of the view and hides everything deeper.
+fn expand_to_depth(app: &mut App, depth: usize) {
+    task_utils::expand_to_depth(app.view_root_mut(), depth.max(1));
+    app.sync_selection();
+}
+
 fn select_next_task(app: &mut App) {
     let task_count = app.get_tasks_to_display().len();
     if task_count == 0 {
@@ -92,7 +535,7 @@ fn select_next_task(app: &mut App) {
         }
         None => 0,
     };
-    app.task_list_state.select(Some(i));
+    app.select_index(Some(i));
 }
 
 fn select_previous_task(app: &mut App) {
@@ -110,5 +553,5 @@ fn select_previous_task(app: &mut App) {
         }
         None => 0,
     };
-    app.task_list_state.select(Some(i));
+    app.select_index(Some(i));
 }
@@ -0,0 +1,103 @@
+use super::{clamp_list_state, App};
+use crate::app::location::Location;
+use crate::app::task::{Task, TaskId};
+use crate::storage;
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+/// A completed subtree moved out of the active tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedTask {
+    pub task: Task,
+    #[serde(flatten)]
+    pub location: Location,
+    pub archived_at: DateTime<Local>,
+}
+
+impl App {
+    /// Moves the completed ones among the tasks, with their subtrees, into the archive.
+    pub fn archive_tasks(&mut self, ids: &[TaskId]) {
+        let completed: Vec<TaskId> = ids
+            .iter()
+            .copied()
+            .filter(|&id| self.task(id).is_some_and(|task| task.completed))
+            .collect();
+        let skipped = ids.len() - completed.len();
+        let archived = self.archive_by_ids(&completed);
+        self.marked.clear();
+        let archived = match archived {
+            Ok(archived) => archived,
+            Err(err) => {
+                self.status_message = Some(format!("Archived, but not saved: {}", err));
+                return;
+            }
+        };
+        self.status_message = Some(if skipped > 0 {
+            format!(
+                "Archived {} task(s), skipped {} still open",
+                archived, skipped
+            )
+        } else {
+            format!("Archived {} task(s)", archived)
+        });
+    }
+
+    /// Archives every completed subtree that was completed at least `days` days ago.
+    pub fn archive_completed(&mut self, days: u32) {
+        let cutoff = Local::now() - TimeDelta::days(i64::from(days));
+        let mut ids = Vec::new();
+        collect_completed_before(&self.tasks, cutoff, &mut ids);
+        self.status_message = Some(match self.archive_by_ids(&ids) {
+            Ok(archived) => format!(
+                "Archived {} task(s) completed over {} day(s) ago",
+                archived, days
+            ),
+            Err(err) => format!("Archived, but not saved: {}", err),
+        });
+    }
+
+    /// Puts an archived task back where it came from, or as close to it as still exists.
+    pub fn restore_archived(&mut self, id: TaskId) {
+        let Some(archive_index) = self.archive.iter().position(|entry| entry.task.id == id) else {
+            return;
+        };
+        let entry = self.archive.remove(archive_index);
+        clamp_list_state(&mut self.archive_list_state, self.archive.len());
+        let restored = self.reinsert_task(entry.task, &entry.location);
+        // Otherwise a crash would leave the task in the archive as well as in the tree
+        self.status_message = Some(match storage::save_archive(&self.config, &self.archive) {
+            Ok(()) => restored,
+            Err(err) => format!("{}, archive not saved: {}", restored, err),
+        });
+    }
+
+    /// Removes the tasks from the tree into the archive and returns how many were archived.
+    /// The archive is saved straight away, since the journal only records the tree and a
+    /// crash would otherwise lose the subtrees.
+    fn archive_by_ids(&mut self, ids: &[TaskId]) -> color_eyre::Result<usize> {
+        let archived_at = Local::now();
+        let taken = self.take_tasks(ids);
+        let count = taken.len();
+        self.archive
+            .extend(taken.into_iter().map(|(task, location)| ArchivedTask {
+                task,
+                location,
+                archived_at,
+            }));
+        if count > 0 {
+            storage::save_archive(&self.config, &self.archive)?;
+        }
+        Ok(count)
+    }
+}
+
+/// Collects the outermost completed tasks that were completed before `cutoff`.
+fn collect_completed_before(tasks: &[Task], cutoff: DateTime<Local>, ids: &mut Vec<TaskId>) {
+    for task in tasks {
+        if task.completed && task.completed_at.is_some_and(|at| at <= cutoff) {
+            ids.push(task.id);
+        } else {
+            collect_completed_before(&task.subtasks, cutoff, ids);
+        }
+    }
+}
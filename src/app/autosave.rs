@@ -0,0 +1,83 @@
+use super::{App, AppMode};
+use crate::app::flat;
+use crate::event::AppEvent;
+use crate::journal::{self, JournalEntry};
+use crate::storage;
+use std::time::Instant;
+
+impl App {
+    /// Writes whatever changed in the tree since the last call to the journal, taking a
+    /// snapshot once enough entries have piled up.
+    pub fn record_changes(&mut self) {
+        let current = flat::flatten(&self.tasks);
+        let ops = flat::diff(&self.saved_tree, &current);
+        if ops.is_empty() {
+            return;
+        }
+        if let Err(err) = self.journal.append(ops) {
+            self.status_message = Some(format!("Autosave failed: {}", err));
+            return;
+        }
+        self.saved_tree = current;
+        self.publish_tasks();
+        if self.config.storage.sync_seconds > 0 {
+            self.unsaved_since = Some(Instant::now());
+        }
+        self.mark_uncommitted();
+        self.backup_if_due();
+        if self.journal.entries_since_snapshot() >= self.config.storage.snapshot_every.max(1) {
+            if let Err(err) = self.snapshot() {
+                self.status_message = Some(format!("Snapshot failed: {}", err));
+            }
+        }
+    }
+
+    /// Saves the tree, archive and trash in full and empties the journal. Changes another
+    /// instance saved to the data file in the meantime are merged in rather than overwritten.
+    pub fn snapshot(&mut self) -> color_eyre::Result<()> {
+        self.merge_external_changes()?;
+        storage::save_archive(&self.config, &self.archive)?;
+        storage::save_trash(&self.config, &self.trash)?;
+        self.disk_version = storage::save_tasks(&self.config, &self.tasks)?;
+        self.disk_tree = flat::flatten(&self.tasks);
+        self.saved_tree = self.disk_tree.clone();
+        self.unsaved_since = None;
+        self.publish_tasks();
+        self.journal.clear()
+    }
+
+    /// Asks whether to replay changes left in the journal by a session that did not shut
+    /// down cleanly.
+    pub fn offer_recovery(&mut self, entries: Vec<JournalEntry>) {
+        if entries.is_empty() {
+            return;
+        }
+        self.mode = AppMode::Confirm {
+            message: format!(
+                "vinegar did not shut down cleanly. Replay {} unsaved change(s)?",
+                entries.len()
+            ),
+            on_confirm: AppEvent::ReplayJournal,
+        };
+        self.recovered = entries;
+    }
+
+    /// Applies the recovered journal on top of the last snapshot and saves the result.
+    pub fn replay_journal(&mut self) {
+        let entries = std::mem::take(&mut self.recovered);
+        let entry_count = entries.len();
+        let mut tree = flat::flatten(&self.tasks);
+        for entry in entries {
+            flat::apply(&mut tree, entry.ops);
+        }
+        self.tasks = flat::unflatten(&tree);
+        self.reindex();
+        let result = self
+            .snapshot()
+            .and_then(|()| journal::discard_recovered(&self.config));
+        self.status_message = Some(match result {
+            Ok(()) => format!("Recovered {} change(s)", entry_count),
+            Err(err) => format!("Recovered changes could not be saved: {}", err),
+        });
+    }
+}
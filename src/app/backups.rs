@@ -0,0 +1,81 @@
+use super::{App, AppMode};
+use crate::backup;
+use std::path::Path;
+
+impl App {
+    /// Takes a backup of the tree if the configured interval has passed since the last one.
+    pub fn backup_if_due(&mut self) {
+        if !backup::is_due(&self.config, self.last_backup) {
+            return;
+        }
+        match backup::create_backup(&self.config, &self.tasks) {
+            Ok(backup) => self.last_backup = Some(backup.created),
+            Err(err) => self.status_message = Some(format!("Backup failed: {}", err)),
+        }
+    }
+
+    /// Lists the backups and opens the browser on the newest one.
+    pub fn open_backup_browser(&mut self) {
+        match backup::list_backups(&self.config) {
+            Ok(backups) => self.backups = backups,
+            Err(err) => {
+                self.status_message = Some(format!("Could not list backups: {}", err));
+                return;
+            }
+        }
+        if self.backups.is_empty() {
+            self.status_message = Some(format!("No backups in {}", self.config.backup.dir));
+            return;
+        }
+        self.mode = AppMode::Backups;
+        self.backup_list_state.select(Some(0));
+        self.load_backup_preview();
+    }
+
+    /// Moves the backup browser selection by `offset` rows and previews the backup there.
+    pub fn select_backup(&mut self, offset: isize) {
+        let Some(selected) = self.backup_list_state.selected() else {
+            return;
+        };
+        let last = self.backups.len().saturating_sub(1);
+        let selected = selected.saturating_add_signed(offset).min(last);
+        self.backup_list_state.select(Some(selected));
+        self.load_backup_preview();
+    }
+
+    fn load_backup_preview(&mut self) {
+        let selected = self.backup_list_state.selected();
+        self.backup_preview = match selected.and_then(|index| self.backups.get(index)) {
+            Some(selected_backup) => match backup::load_backup(&selected_backup.path) {
+                Ok(tasks) => tasks,
+                Err(err) => {
+                    self.status_message = Some(format!("Could not read backup: {}", err));
+                    Vec::new()
+                }
+            },
+            None => Vec::new(),
+        };
+    }
+
+    /// Replaces the tree with a backup, keeping a backup of the current tree first.
+    pub fn restore_backup(&mut self, path: &Path) {
+        let tasks = match backup::load_backup(path) {
+            Ok(tasks) => tasks,
+            Err(err) => {
+                self.status_message = Some(format!("Could not read backup: {}", err));
+                return;
+            }
+        };
+        if let Err(err) = backup::create_backup(&self.config, &self.tasks) {
+            self.status_message = Some(format!("Backup failed, nothing restored: {}", err));
+            return;
+        }
+        self.tasks = tasks;
+        self.reindex();
+        self.select_index(None);
+        self.status_message = Some(match self.snapshot() {
+            Ok(()) => format!("Restored backup {}", path.display()),
+            Err(err) => format!("Restored backup, but saving failed: {}", err),
+        });
+    }
+}
@@ -0,0 +1,159 @@
+use super::{App, AppMode};
+use crate::app::export;
+use crate::app::task::{Task, TaskId};
+use crate::app::task_utils;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+
+impl App {
+    /// Every marked task, including the live range while in visual mode.
+    pub fn marked_ids(&self) -> HashSet<TaskId> {
+        let mut marked = self.marked.clone();
+        if let AppMode::Visual { anchor } = self.mode {
+            if let Some(selected_index) = self.task_list_state.selected() {
+                let (start, end) = (anchor.min(selected_index), anchor.max(selected_index));
+                let tasks_to_display = self.get_tasks_to_display();
+                // Group headers have no task behind them
+                marked.extend(
+                    tasks_to_display
+                        .iter()
+                        .take(end + 1)
+                        .skip(start)
+                        .filter_map(|(_, id)| *id),
+                );
+            }
+        }
+        marked
+    }
+
+    /// Folds the visual range into the marked set and leaves visual mode.
+    pub fn commit_visual_range(&mut self) {
+        if let AppMode::Visual { .. } = self.mode {
+            self.marked = self.marked_ids();
+            self.mode = AppMode::Normal;
+        }
+    }
+
+    /// The tasks a bulk action applies to: the marked set, or the selected task if nothing is
+    /// marked.
+    pub fn target_ids(&self) -> Vec<TaskId> {
+        let marked = self.marked_ids();
+        if !marked.is_empty() {
+            return marked.into_iter().collect();
+        }
+        self.selected_id().into_iter().collect()
+    }
+
+    /// Paths of the tasks with `ids` in tree order, leaving out any task that lies inside
+    /// another one of them.
+    pub fn outermost_paths(&self, ids: &[TaskId]) -> Vec<Vec<usize>> {
+        let paths: BTreeSet<Vec<usize>> = ids.iter().filter_map(|&id| self.path_of(id)).collect();
+        task_utils::outermost_paths(&paths)
+    }
+
+    /// Completes the tasks, or reopens them all if they are already complete. Tasks still
+    /// waiting on open blockers are left open unless `force` is set.
+    pub fn toggle_complete(&mut self, ids: &[TaskId], force: bool) {
+        let all_completed = ids
+            .iter()
+            .all(|&id| self.task(id).is_some_and(|task| task.completed));
+        let (ids, blocked): (Vec<TaskId>, Vec<TaskId>) = ids.iter().partition(|&&id| {
+            all_completed || force || !self.task(id).is_some_and(|task| self.is_blocked(task))
+        });
+        if !blocked.is_empty() {
+            self.status_message = Some(format!("Skipped {} blocked task(s)", blocked.len()));
+        }
+        for &id in &ids {
+            if let Some(task) = self.task_mut(id) {
+                task.set_completed(!all_completed);
+            }
+        }
+        if !all_completed {
+            for &id in &ids {
+                self.schedule_next_occurrence(id);
+            }
+        }
+    }
+
+    /// Adds the next occurrence of a completed recurring task right after it.
+    fn schedule_next_occurrence(&mut self, id: TaskId) {
+        let Some(path) = self.path_of(id) else {
+            return;
+        };
+        let Some(next) = task_utils::get_task_mut(&mut self.tasks, &path)
+            .filter(|task| task.completed)
+            .and_then(Task::next_occurrence)
+        else {
+            return;
+        };
+        let (position, parent_path) = path.split_last().unwrap();
+        if let Some(siblings) = task_utils::get_children_mut(&mut self.tasks, parent_path) {
+            self.status_message = Some(match next.due {
+                Some(due) => format!("Next occurrence due {}", due.format("%Y-%m-%d")),
+                None => "Added next occurrence".to_string(),
+            });
+            siblings.insert(position + 1, next);
+        }
+        // Later tasks in the same batch may have shifted
+        self.reindex();
+    }
+
+    /// Moves the tasks and their subtrees under `parent`, or to the top level.
+    pub fn move_tasks(&mut self, ids: &[TaskId], parent: Option<TaskId>) {
+        let parent_path = match parent {
+            Some(parent_id) => match self.path_of(parent_id) {
+                Some(path) => path,
+                None => return,
+            },
+            None => Vec::new(),
+        };
+        // A task cannot be moved into itself or into one of its own subtasks.
+        let paths: Vec<Vec<usize>> = self
+            .outermost_paths(ids)
+            .into_iter()
+            .filter(|path| !parent_path.starts_with(path))
+            .collect();
+        let moved = task_utils::remove_tasks(&mut self.tasks, &paths);
+        let moved_count = moved.len();
+        // Removing the tasks may have shifted the parent, so look it up again
+        self.reindex();
+        let parent_path = parent
+            .and_then(|parent_id| self.path_of(parent_id))
+            .unwrap_or_default();
+        if let Some(children) = task_utils::get_children_mut(&mut self.tasks, &parent_path) {
+            children.extend(moved);
+        }
+        if let Some(parent_task) = task_utils::get_task_mut(&mut self.tasks, &parent_path) {
+            parent_task.expanded = true;
+        }
+        self.marked.clear();
+        self.status_message = Some(format!("Moved {} task(s)", moved_count));
+    }
+
+    /// Adds `tag` to every one of the tasks.
+    pub fn tag_tasks(&mut self, ids: &[TaskId], tag: &str) {
+        let tag = tag.trim().trim_start_matches('#');
+        if tag.is_empty() {
+            return;
+        }
+        for &id in ids {
+            if let Some(task) = self.task_mut(id) {
+                task.add_tag(tag);
+            }
+        }
+    }
+
+    /// Writes the task subtrees to the configured export file as Markdown.
+    pub fn export_tasks(&mut self, ids: &[TaskId]) {
+        let tasks: Vec<_> = self
+            .outermost_paths(ids)
+            .iter()
+            .filter_map(|path| task_utils::get_task(&self.tasks, path).cloned())
+            .collect();
+        let export_path = &self.config.export.path;
+        self.status_message = Some(match fs::write(export_path, export::to_markdown(&tasks)) {
+            Ok(()) => format!("Exported {} task(s) to {}", tasks.len(), export_path),
+            Err(err) => format!("Export failed: {}", err),
+        });
+    }
+}
@@ -0,0 +1,158 @@
+use super::App;
+use crate::app::flat::{self, FlatOp, FlatTask};
+use crate::caldav::{self, CalDavClient, LocalChange, SyncOutcome};
+use crate::event::{AppEvent, Event};
+use crate::storage;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+impl App {
+    /// Loads what was synced with the CalDAV collection last session.
+    pub fn open_calendar(&mut self) {
+        if !self.config.caldav.enabled {
+            return;
+        }
+        match storage::read_json(Path::new(&self.config.caldav.state_path)) {
+            Ok(state) => self.calendar_state = state,
+            Err(err) => {
+                self.status_message = Some(format!("Calendar sync state not loaded: {}", err))
+            }
+        }
+    }
+
+    /// Starts a sync once `interval_seconds` passed since the last one, and right away on start.
+    pub fn sync_calendar_if_due(&mut self) {
+        let caldav = &self.config.caldav;
+        if !caldav.enabled || self.calendar_syncing {
+            return;
+        }
+        let due = match self.last_calendar_sync {
+            None => true,
+            Some(_) if caldav.interval_seconds == 0 => false,
+            Some(last) => last.elapsed() >= Duration::from_secs(caldav.interval_seconds),
+        };
+        if due {
+            self.sync_calendar();
+        }
+    }
+
+    /// Syncs with the CalDAV collection in the background. The result comes back as
+    /// [`AppEvent::CalendarSynced`].
+    pub fn sync_calendar(&mut self) {
+        if !self.config.caldav.enabled {
+            self.status_message = Some("Calendar sync is not enabled in [caldav]".to_string());
+            return;
+        }
+        if self.calendar_syncing {
+            self.status_message = Some("Calendar sync already running".to_string());
+            return;
+        }
+        self.last_calendar_sync = Some(Instant::now());
+        let client = match CalDavClient::new(&self.config.caldav) {
+            Ok(client) => client,
+            Err(err) => {
+                self.status_message = Some(format!("Calendar sync failed: {}", err));
+                return;
+            }
+        };
+        self.calendar_syncing = true;
+        let tree = flat::flatten(&self.tasks);
+        let state = self.calendar_state.clone();
+        let sender = self.events.sender();
+        tokio::spawn(async move {
+            let result = caldav::sync(&client, tree, state)
+                .await
+                .map(Box::new)
+                .map_err(|err| err.to_string());
+            let _ = sender.send(Event::App(AppEvent::CalendarSynced { result }));
+        });
+    }
+
+    /// Applies what a sync brought back, skipping tasks that were edited while it ran; the
+    /// next sync merges those.
+    pub fn finish_calendar_sync(&mut self, result: Result<Box<SyncOutcome>, String>) {
+        self.calendar_syncing = false;
+        let outcome = match result {
+            Ok(outcome) => *outcome,
+            Err(err) => {
+                self.status_message = Some(format!("Calendar sync failed: {}", err));
+                return;
+            }
+        };
+        let SyncOutcome {
+            changes,
+            mut state,
+            pushed,
+            pulled,
+            conflicts,
+        } = outcome;
+        let mut tree = flat::flatten(&self.tasks);
+        let mut removed = Vec::new();
+        let mut skipped = 0;
+        for LocalChange { seen, op, previous } in changes {
+            let id = match &op {
+                FlatOp::Put(entry) => entry.task.id,
+                FlatOp::Remove(id) => *id,
+            };
+            let current = tree.get(&id);
+            if !same_entry(current, seen.as_ref()) {
+                match previous {
+                    Some(previous) => state.insert(id, previous),
+                    None => state.remove(&id),
+                };
+                skipped += 1;
+                continue;
+            }
+            match op {
+                FlatOp::Put(mut entry) => {
+                    // Keep what is expanded and the order among unchanged siblings
+                    if let Some(current) = current {
+                        entry.task.expanded = current.task.expanded;
+                        if entry.parent == current.parent {
+                            entry.position = current.position;
+                        }
+                    }
+                    flat::apply(&mut tree, [FlatOp::Put(entry)]);
+                }
+                FlatOp::Remove(id) => removed.push(id),
+            }
+        }
+        self.tasks = flat::unflatten(&tree);
+        self.reindex();
+        if !removed.is_empty() {
+            self.delete_tasks(&removed);
+        }
+        self.calendar_state = state;
+        let path = Path::new(&self.config.caldav.state_path);
+        if let Err(err) = storage::write_json(path, &self.calendar_state) {
+            self.status_message = Some(format!("Calendar sync state not saved: {}", err));
+            return;
+        }
+        let mut message = format!(
+            "Calendar synced: {} pulled, {} pushed",
+            pulled - skipped,
+            pushed
+        );
+        if conflicts > 0 {
+            message.push_str(&format!(", {} conflict(s) kept as here", conflicts));
+        }
+        if skipped > 0 {
+            message.push_str(&format!(", {} left for next time", skipped));
+        }
+        self.status_message = Some(message);
+    }
+}
+
+/// Whether a task is as it was when the sync started, apart from being expanded or moved
+/// among its siblings.
+fn same_entry(current: Option<&FlatTask>, seen: Option<&FlatTask>) -> bool {
+    match (current, seen) {
+        (Some(current), Some(seen)) => {
+            let mut task = current.task.clone();
+            task.expanded = seen.task.expanded;
+            task == seen.task && current.parent == seen.parent
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,212 @@
+use super::App;
+use crate::app::export;
+use crate::app::task::{Task, TaskId};
+use crate::app::task_utils;
+use std::io::{self, Write};
+
+impl App {
+    /// Copies the task subtrees into the clipboard.
+    pub fn yank_tasks(&mut self, ids: &[TaskId]) {
+        self.clipboard = self
+            .outermost_paths(ids)
+            .iter()
+            .filter_map(|path| task_utils::get_task(&self.tasks, path).cloned())
+            .collect();
+        self.marked.clear();
+        self.status_message = Some(format!("Yanked {} task(s)", self.clipboard.len()));
+        self.copy_clipboard_to_terminal();
+    }
+
+    /// Moves the task subtrees out of the tree and into the clipboard.
+    pub fn cut_tasks(&mut self, ids: &[TaskId]) {
+        let paths = self.outermost_paths(ids);
+        self.stop_timer_within(&paths);
+        self.clipboard = task_utils::remove_tasks(&mut self.tasks, &paths);
+        self.marked.clear();
+        self.status_message = Some(format!("Cut {} task(s)", self.clipboard.len()));
+        self.copy_clipboard_to_terminal();
+    }
+
+    /// Inserts the clipboard after `target`, or inside it if `as_child` is set. Without a
+    /// target the clipboard goes to the end of the top level.
+    ///
+    /// A cut subtree keeps its IDs while none of them is in the tree, so cut and paste acts as
+    /// a move. Every other paste is a copy with fresh IDs.
+    pub fn paste(&mut self, target: Option<TaskId>, as_child: bool) {
+        if self.clipboard.is_empty() {
+            self.status_message = Some("Clipboard is empty".to_string());
+            return;
+        }
+        let pasted: Vec<Task> = self
+            .clipboard
+            .iter()
+            .map(|task| {
+                // Keeping the IDs would duplicate any of them still in the tree
+                if task.any_in_subtree(&|task| self.index.contains_key(&task.id)) {
+                    task.with_fresh_ids()
+                } else {
+                    task.clone()
+                }
+            })
+            .collect();
+        let first_pasted_id = pasted[0].id;
+        let target_path = match target {
+            Some(target_id) => match self.path_of(target_id) {
+                Some(path) => Some(path),
+                // The target was removed before the paste arrived
+                None => return,
+            },
+            None => None,
+        };
+        match target_path {
+            Some(path) if as_child => {
+                let Some(parent) = task_utils::get_task_mut(&mut self.tasks, &path) else {
+                    return;
+                };
+                parent.subtasks.extend(pasted);
+                parent.expanded = true;
+            }
+            Some(path) => {
+                let (task_index, parent_path) = path.split_last().unwrap();
+                let insert_at = task_index + 1;
+                let Some(siblings) = task_utils::get_children_mut(&mut self.tasks, parent_path)
+                else {
+                    return;
+                };
+                siblings.splice(insert_at..insert_at, pasted);
+            }
+            None => self.tasks.extend(pasted),
+        }
+        self.reindex();
+        self.select_id(first_pasted_id);
+    }
+
+    /// Hands the clipboard to the terminal as Markdown through an OSC 52 escape sequence, so it
+    /// can be pasted into other applications.
+    fn copy_clipboard_to_terminal(&self) {
+        if !self.config.clipboard.osc52 {
+            return;
+        }
+        let markdown = export::to_markdown(&self.clipboard);
+        let mut stdout = io::stdout();
+        // The clipboard is a convenience; a terminal that rejects the sequence is not an error
+        let _ = write!(
+            stdout,
+            "\x1b]52;c;{}\x07",
+            base64_encode(markdown.as_bytes())
+        );
+        let _ = stdout.flush();
+    }
+}
+
+/// Standard base64 with padding, as OSC 52 and HTTP Basic authentication expect.
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|task| task.name.as_str()).collect()
+    }
+
+    /// Every task in the tree, counting duplicated IDs separately.
+    fn count(tasks: &[Task]) -> usize {
+        tasks.iter().map(|task| 1 + task.descendant_count()).sum()
+    }
+
+    fn tree() -> Vec<Task> {
+        let mut trip = Task::new("Plan trip".to_string());
+        trip.subtasks.push(Task::new("Book flights".to_string()));
+        vec![trip, Task::new("Water plants".to_string())]
+    }
+
+    // The event loop reindexes after every event, so the tests do the same between calls
+
+    #[tokio::test]
+    async fn cut_and_paste_moves_the_subtree() {
+        let tasks = tree();
+        let (trip, plants) = (tasks[0].clone(), tasks[1].id);
+        let mut app = App::for_tests(tasks);
+
+        app.cut_tasks(&[trip.id]);
+        app.reindex();
+        app.paste(Some(plants), false);
+
+        assert_eq!(names(&app.tasks), ["Water plants", "Plan trip"]);
+        assert_eq!(app.tasks[1], trip);
+    }
+
+    #[tokio::test]
+    async fn pastes_inside_the_target_as_a_child() {
+        let tasks = tree();
+        let (trip, plants) = (tasks[0].id, tasks[1].id);
+        let mut app = App::for_tests(tasks);
+
+        app.yank_tasks(&[plants]);
+        app.paste(Some(trip), true);
+
+        assert_eq!(
+            names(&app.tasks[0].subtasks),
+            ["Book flights", "Water plants"]
+        );
+        // The original is still there, so the copy needs its own ID
+        assert_ne!(app.tasks[0].subtasks[1].id, plants);
+        assert_eq!(app.selected_id(), Some(app.tasks[0].subtasks[1].id));
+    }
+
+    #[tokio::test]
+    async fn pasting_again_makes_a_copy() {
+        let tasks = tree();
+        let trip = tasks[0].id;
+        let mut app = App::for_tests(tasks);
+
+        app.cut_tasks(&[trip]);
+        app.reindex();
+        app.paste(None, false);
+        app.paste(None, false);
+
+        assert_eq!(
+            names(&app.tasks),
+            ["Water plants", "Plan trip", "Plan trip"]
+        );
+        assert_eq!(app.index.len(), count(&app.tasks));
+    }
+
+    #[tokio::test]
+    async fn gives_fresh_ids_when_a_subtask_is_still_in_the_tree() {
+        let tasks = tree();
+        let (trip, flights) = (tasks[0].id, tasks[0].subtasks[0].id);
+        let mut app = App::for_tests(tasks);
+
+        app.cut_tasks(&[trip]);
+        // One of the cut subtasks comes back on its own before the paste
+        app.tasks.push(app.clipboard[0].subtasks[0].clone());
+        app.reindex();
+        app.paste(None, false);
+
+        assert_eq!(count(&app.tasks), 4);
+        assert_eq!(app.index.len(), 4);
+        assert_eq!(app.tasks[1].id, flights);
+        assert_ne!(app.tasks[2].id, trip);
+    }
+}
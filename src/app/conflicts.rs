@@ -0,0 +1,71 @@
+use super::{clamp_list_state, App, AppMode};
+use crate::app::flat;
+use crate::app::merge::{self, Conflict, Side};
+use crate::app::task::TaskId;
+
+impl App {
+    /// Opens the conflict screen on the conflicts left by a merge. If something is being
+    /// typed or confirmed they are left as merged instead.
+    pub fn add_conflicts(&mut self, conflicts: Vec<Conflict>) {
+        if conflicts.is_empty() {
+            return;
+        }
+        if !matches!(self.mode, AppMode::Normal | AppMode::Conflicts) {
+            self.status_message = Some(format!(
+                "Left {} merge conflict(s) as merged",
+                conflicts.len()
+            ));
+            return;
+        }
+        self.conflicts
+            .retain(|old| !conflicts.iter().any(|new| new.id == old.id));
+        self.conflicts.extend(conflicts);
+        self.mode = AppMode::Conflicts;
+        if self.conflict_list_state.selected().is_none() {
+            self.conflict_list_state.select(Some(0));
+        }
+    }
+
+    /// Moves the conflict screen selection by `offset` rows.
+    pub fn select_conflict(&mut self, offset: isize) {
+        let Some(selected) = self.conflict_list_state.selected() else {
+            return;
+        };
+        let last = self.conflicts.len().saturating_sub(1);
+        self.conflict_list_state
+            .select(Some(selected.saturating_add_signed(offset).min(last)));
+    }
+
+    /// Settles the conflict on task `id` with `side`'s version, closing the conflict screen
+    /// once none are left.
+    pub fn resolve_conflict(&mut self, id: TaskId, side: Side) {
+        let Some(position) = self.conflicts.iter().position(|conflict| conflict.id == id) else {
+            return;
+        };
+        let conflict = self.conflicts.remove(position);
+        let mut tree = flat::flatten(&self.tasks);
+        merge::resolve(&mut tree, &conflict, side);
+        self.tasks = flat::unflatten(&tree);
+        self.reindex();
+        clamp_list_state(&mut self.conflict_list_state, self.conflicts.len());
+        if self.conflicts.is_empty() {
+            if self.mode == AppMode::Conflicts {
+                self.mode = AppMode::Normal;
+            }
+            self.status_message = Some("All merge conflicts resolved".to_string());
+        }
+    }
+
+    /// Leaves the remaining conflicts as merged and closes the conflict screen.
+    pub fn dismiss_conflicts(&mut self) {
+        if !self.conflicts.is_empty() {
+            self.status_message = Some(format!(
+                "Left {} merge conflict(s) as merged",
+                self.conflicts.len()
+            ));
+        }
+        self.conflicts.clear();
+        self.conflict_list_state.select(None);
+        self.mode = AppMode::Normal;
+    }
+}
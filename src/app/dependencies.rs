@@ -0,0 +1,144 @@
+use super::App;
+use crate::app::task::{Task, TaskId};
+use crate::app::view::compare_tasks;
+use std::collections::HashSet;
+
+impl App {
+    /// The tasks `task` is waiting on that are still open. Blockers that were deleted no
+    /// longer count.
+    pub fn open_blockers(&self, task: &Task) -> Vec<&Task> {
+        task.blocked_by
+            .iter()
+            .filter_map(|&id| self.task(id))
+            .filter(|blocker| !blocker.completed)
+            .collect()
+    }
+
+    /// Whether `task` is waiting on any open task.
+    pub fn is_blocked(&self, task: &Task) -> bool {
+        !self.open_blockers(task).is_empty()
+    }
+
+    /// Whether `id` waits on `target`, directly or through a chain of other blockers.
+    pub fn depends_on(&self, id: TaskId, target: TaskId) -> bool {
+        let mut visited = HashSet::new();
+        let mut pending = vec![id];
+        while let Some(current) = pending.pop() {
+            if current == target {
+                return true;
+            }
+            if !visited.insert(current) {
+                continue;
+            }
+            if let Some(task) = self.task(current) {
+                pending.extend(task.blocked_by.iter().copied());
+            }
+        }
+        false
+    }
+
+    /// Makes the task wait on `blockers`, refusing any blocker that would close a cycle.
+    pub fn block_task(&mut self, id: TaskId, blockers: &[TaskId]) {
+        let (accepted, cyclic): (Vec<TaskId>, Vec<TaskId>) = blockers
+            .iter()
+            .copied()
+            .filter(|&blocker| blocker != id && self.index.contains_key(&blocker))
+            .partition(|&blocker| !self.depends_on(blocker, id));
+        if let Some(task) = self.task_mut(id) {
+            for blocker in &accepted {
+                if !task.blocked_by.contains(blocker) {
+                    task.blocked_by.push(*blocker);
+                }
+            }
+        }
+        self.marked.clear();
+        self.status_message = Some(if cyclic.is_empty() {
+            format!("Blocked by {} task(s)", accepted.len())
+        } else {
+            format!(
+                "Blocked by {} task(s), skipped {} that would create a cycle",
+                accepted.len(),
+                cyclic.len()
+            )
+        });
+    }
+
+    /// Removes every dependency of the task.
+    pub fn unblock_task(&mut self, id: TaskId) {
+        if let Some(task) = self.task_mut(id) {
+            task.blocked_by.clear();
+            self.status_message = Some("Cleared dependencies".to_string());
+        }
+    }
+
+    /// Open, unblocked tasks in the view with no open subtasks: what can be worked on now.
+    pub(super) fn get_actionable_tasks_to_display(&self) -> Vec<(String, Option<TaskId>)> {
+        let mut actionable = Vec::new();
+        self.collect_actionable(self.view_root(), &mut actionable);
+        if let Some(sort_key) = self.sort {
+            actionable.sort_by(|a, b| compare_tasks(a, b, sort_key));
+        }
+        actionable
+            .into_iter()
+            .map(|task| (task.name.clone(), Some(task.id)))
+            .collect()
+    }
+
+    fn collect_actionable<'a>(&self, tasks: &'a [Task], actionable: &mut Vec<&'a Task>) {
+        for task in tasks {
+            if task.completed {
+                continue;
+            }
+            let has_open_subtasks = task.subtasks.iter().any(|subtask| !subtask.completed);
+            if !has_open_subtasks && !self.is_blocked(task) {
+                actionable.push(task);
+            }
+            self.collect_actionable(&task.subtasks, actionable);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn refuses_blockers_that_would_close_a_cycle() {
+        let tasks = vec![
+            Task::new("Book flights".to_string()),
+            Task::new("Book hotel".to_string()),
+            Task::new("Pack".to_string()),
+        ];
+        let (flights, hotel, pack) = (tasks[0].id, tasks[1].id, tasks[2].id);
+        let mut app = App::for_tests(tasks);
+        app.block_task(hotel, &[flights]);
+        app.block_task(pack, &[hotel]);
+
+        // Flights would wait on itself through the hotel and packing
+        app.block_task(flights, &[pack, flights]);
+
+        assert!(app.task(flights).unwrap().blocked_by.is_empty());
+        assert!(app.depends_on(pack, flights));
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Blocked by 0 task(s), skipped 1 that would create a cycle")
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_the_blockers_that_are_safe() {
+        let tasks = vec![
+            Task::new("Book flights".to_string()),
+            Task::new("Book hotel".to_string()),
+            Task::new("Renew passport".to_string()),
+        ];
+        let (flights, hotel, passport) = (tasks[0].id, tasks[1].id, tasks[2].id);
+        let mut app = App::for_tests(tasks);
+        app.block_task(hotel, &[flights]);
+
+        app.block_task(flights, &[hotel, passport]);
+
+        assert_eq!(app.task(flights).unwrap().blocked_by, [passport]);
+        assert!(app.is_blocked(app.task(flights).unwrap()));
+    }
+}
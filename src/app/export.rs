@@ -0,0 +1,27 @@
+use crate::app::task::Task;
+
+/// Renders tasks and their subtrees as a nested Markdown checklist.
+pub fn to_markdown(tasks: &[Task]) -> String {
+    let mut markdown = String::new();
+    for task in tasks {
+        write_markdown(&mut markdown, task, 0);
+    }
+    markdown
+}
+
+fn write_markdown(markdown: &mut String, task: &Task, depth: usize) {
+    let checkbox = if task.completed { "[x]" } else { "[ ]" };
+    markdown.push_str(&format!(
+        "{}- {} {}",
+        "  ".repeat(depth),
+        checkbox,
+        task.name
+    ));
+    for tag in &task.tags {
+        markdown.push_str(&format!(" #{}", tag));
+    }
+    markdown.push('\n');
+    for subtask in &task.subtasks {
+        write_markdown(markdown, subtask, depth + 1);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::app::task::{Task, TaskId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// The task tree keyed by ID, with each task pointing at its parent instead of holding its
+/// subtasks. Two versions of the tree can be compared task by task in this form.
+pub type FlatTree = HashMap<TaskId, FlatTask>;
+
+/// One task without its subtasks, plus where it sits in the tree.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlatTask {
+    #[serde(flatten)]
+    pub task: Task,
+    pub parent: Option<TaskId>,
+    /// Index among its siblings.
+    pub position: usize,
+}
+
+/// A change to a single task in a [`FlatTree`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FlatOp {
+    /// Add the task, or replace the version with the same ID.
+    Put(Box<FlatTask>),
+    Remove(TaskId),
+}
+
+/// Flattens the tree.
+pub fn flatten(tasks: &[Task]) -> FlatTree {
+    let mut flat = FlatTree::new();
+    flatten_into(tasks, None, &mut flat);
+    flat
+}
+
+fn flatten_into(tasks: &[Task], parent: Option<TaskId>, flat: &mut FlatTree) {
+    for (position, task) in tasks.iter().enumerate() {
+        let mut shallow = task.clone();
+        shallow.subtasks = Vec::new();
+        flat.insert(
+            task.id,
+            FlatTask {
+                task: shallow,
+                parent,
+                position,
+            },
+        );
+        flatten_into(&task.subtasks, Some(task.id), flat);
+    }
+}
+
+/// Rebuilds the tree. Tasks whose parent is missing end up at the top level.
+pub fn unflatten(flat: &FlatTree) -> Vec<Task> {
+    let mut children: HashMap<Option<TaskId>, Vec<&FlatTask>> = HashMap::new();
+    for entry in flat.values() {
+        let parent = entry.parent.filter(|parent| flat.contains_key(parent));
+        children.entry(parent).or_default().push(entry);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by_key(|entry| (entry.position, entry.task.id));
+    }
+    let mut placed = HashSet::new();
+    let mut tasks = build_children(None, &children, &mut placed);
+    // Tasks caught in a parent cycle are never reached from the top; lift them out
+    let mut stranded: Vec<&FlatTask> = flat
+        .values()
+        .filter(|entry| !placed.contains(&entry.task.id))
+        .collect();
+    stranded.sort_by_key(|entry| (entry.position, entry.task.id));
+    for entry in stranded {
+        if placed.insert(entry.task.id) {
+            let mut task = entry.task.clone();
+            task.subtasks = build_children(Some(task.id), &children, &mut placed);
+            tasks.push(task);
+        }
+    }
+    tasks
+}
+
+fn build_children(
+    parent: Option<TaskId>,
+    children: &HashMap<Option<TaskId>, Vec<&FlatTask>>,
+    placed: &mut HashSet<TaskId>,
+) -> Vec<Task> {
+    let Some(siblings) = children.get(&parent) else {
+        return Vec::new();
+    };
+    let mut tasks = Vec::new();
+    for entry in siblings {
+        if !placed.insert(entry.task.id) {
+            continue;
+        }
+        let mut task = entry.task.clone();
+        task.subtasks = build_children(Some(task.id), children, placed);
+        tasks.push(task);
+    }
+    tasks
+}
+
+/// The operations that turn `old` into `new`.
+pub fn diff(old: &FlatTree, new: &FlatTree) -> Vec<FlatOp> {
+    let mut ops: Vec<FlatOp> = new
+        .values()
+        .filter(|entry| old.get(&entry.task.id) != Some(entry))
+        .map(|entry| FlatOp::Put(Box::new(entry.clone())))
+        .collect();
+    ops.extend(
+        old.keys()
+            .filter(|id| !new.contains_key(id))
+            .map(|&id| FlatOp::Remove(id)),
+    );
+    ops
+}
+
+/// Applies the operations to `flat` in order.
+pub fn apply(flat: &mut FlatTree, ops: impl IntoIterator<Item = FlatOp>) {
+    for op in ops {
+        match op {
+            FlatOp::Put(entry) => {
+                flat.insert(entry.task.id, *entry);
+            }
+            FlatOp::Remove(id) => {
+                flat.remove(&id);
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use super::{App, AppMode};
+use crate::app::flat::{self, FlatTask, FlatTree};
+use crate::app::merge;
+use crate::app::task::TaskId;
+use crate::git::GitStore;
+use std::time::{Duration, Instant};
+
+/// Number of commits listed in the history browser.
+const HISTORY_LIMIT: usize = 200;
+
+impl App {
+    /// Opens the git repository when versioning is enabled and commits the data file if it
+    /// changed outside vinegar since the last commit.
+    pub fn open_git_store(&mut self) {
+        if !self.config.git.enabled {
+            return;
+        }
+        match GitStore::open(&self.config) {
+            Ok(store) => {
+                // Read the conflict stages before anything is staged over them
+                match store.unmerged_versions() {
+                    Ok(Some([base, ours, theirs])) => {
+                        let merged = merge::merge(
+                            &flat::flatten(&base),
+                            &flat::flatten(&ours),
+                            &flat::flatten(&theirs),
+                        );
+                        self.tasks = flat::unflatten(&merged.tree);
+                        self.reindex();
+                        self.add_conflicts(merged.conflicts);
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        self.status_message = Some(format!("Could not read the merge: {}", err))
+                    }
+                }
+                if store.merging() {
+                    self.status_message.get_or_insert_with(|| {
+                        format!(
+                            "Merge in progress: stage and commit {} once it is resolved",
+                            self.config.storage.path
+                        )
+                    });
+                } else {
+                    let message = match store.history(1) {
+                        Ok(history) if !history.is_empty() => "Record changes made outside vinegar",
+                        _ => "Start tracking tasks",
+                    };
+                    if let Err(err) = store.commit(message) {
+                        self.status_message = Some(format!("Git commit failed: {}", err));
+                    }
+                }
+                self.git = Some(store);
+            }
+            Err(err) => self.status_message = Some(format!("Git versioning is off: {}", err)),
+        }
+    }
+
+    /// Notes that the tree changed, so the next quiet moment commits it.
+    pub fn mark_uncommitted(&mut self) {
+        if self.git.is_some() {
+            self.uncommitted_since = Some(Instant::now());
+        }
+    }
+
+    /// Commits the pending changes once none have been made for the configured batch time.
+    pub fn commit_if_settled(&mut self) {
+        let batch = Duration::from_secs(self.config.git.batch_seconds);
+        if self
+            .uncommitted_since
+            .is_some_and(|since| since.elapsed() >= batch)
+        {
+            self.commit_changes();
+        }
+    }
+
+    /// Saves the tree and commits everything that changed since the last commit, described
+    /// in the commit message.
+    pub fn commit_changes(&mut self) {
+        self.uncommitted_since = None;
+        if self.git.is_none() {
+            return;
+        }
+        let current = flat::flatten(&self.tasks);
+        if current == self.committed_tree {
+            return;
+        }
+        let message = commit_message(&describe_changes(&self.committed_tree, &current));
+        self.commit(&message);
+    }
+
+    fn commit(&mut self, message: &str) {
+        if self.git.is_none() {
+            return;
+        }
+        let result = self.snapshot().and_then(|()| match &self.git {
+            Some(store) => store.commit(message),
+            None => Ok(()),
+        });
+        match result {
+            Ok(()) => self.committed_tree = self.saved_tree.clone(),
+            Err(err) => self.status_message = Some(format!("Git commit failed: {}", err)),
+        }
+    }
+
+    /// Lists the commits of the data file and opens the browser on the newest one.
+    pub fn open_history(&mut self) {
+        // Whatever is still waiting belongs at the top of the list
+        self.commit_changes();
+        let Some(store) = &self.git else {
+            self.status_message = Some("Git versioning is not enabled".to_string());
+            return;
+        };
+        match store.history(HISTORY_LIMIT) {
+            Ok(history) => self.history = history,
+            Err(err) => {
+                self.status_message = Some(format!("Could not read the history: {}", err));
+                return;
+            }
+        }
+        if self.history.is_empty() {
+            self.status_message = Some("Nothing has been committed yet".to_string());
+            return;
+        }
+        self.mode = AppMode::History;
+        self.history_list_state.select(Some(0));
+        self.load_commit_changes();
+    }
+
+    /// Moves the history selection by `offset` rows and shows what that commit changed.
+    pub fn select_commit(&mut self, offset: isize) {
+        let Some(selected) = self.history_list_state.selected() else {
+            return;
+        };
+        let last = self.history.len().saturating_sub(1);
+        let selected = selected.saturating_add_signed(offset).min(last);
+        self.history_list_state.select(Some(selected));
+        self.load_commit_changes();
+    }
+
+    /// Diffs the tree in the selected commit against the one in the commit before it.
+    fn load_commit_changes(&mut self) {
+        self.history_changes = Vec::new();
+        let (Some(store), Some(selected)) = (&self.git, self.history_list_state.selected()) else {
+            return;
+        };
+        let Some(commit) = self.history.get(selected) else {
+            return;
+        };
+        let result = store.tasks_at(&commit.hash).and_then(|tasks| {
+            let before = match self.history.get(selected + 1) {
+                Some(parent) => store.tasks_at(&parent.hash)?,
+                None => Vec::new(),
+            };
+            Ok(describe_changes(
+                &flat::flatten(&before),
+                &flat::flatten(&tasks),
+            ))
+        });
+        match result {
+            Ok(changes) => self.history_changes = changes,
+            Err(err) => self.status_message = Some(format!("Could not read the commit: {}", err)),
+        }
+    }
+
+    /// Replaces the tree with the one committed in `hash` and commits the rollback.
+    pub fn roll_back(&mut self, hash: &str) {
+        let Some(store) = &self.git else {
+            return;
+        };
+        let tasks = match store.tasks_at(hash) {
+            Ok(tasks) => tasks,
+            Err(err) => {
+                self.status_message = Some(format!("Could not read the commit: {}", err));
+                return;
+            }
+        };
+        // Keep the changes since the last commit in the history rather than dropping them
+        self.commit_changes();
+        let subject = self
+            .history
+            .iter()
+            .find(|commit| commit.hash == hash)
+            .map_or_else(String::new, |commit| format!(" ({})", commit.subject));
+        self.tasks = tasks;
+        self.reindex();
+        self.select_index(None);
+        self.commit(&format!(
+            "Roll back to {}{}",
+            &hash[..hash.len().min(7)],
+            subject
+        ));
+        self.status_message = Some(format!("Rolled back to {}", &hash[..hash.len().min(7)]));
+    }
+}
+
+/// One line per change that turns `old` into `new`, such as "Add task 'x' under 'y'".
+/// Subtasks that were added, deleted or moved along with their parent are not listed, and
+/// neither are changes to what is expanded or to positions among siblings.
+pub fn describe_changes(old: &FlatTree, new: &FlatTree) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut added: Vec<&FlatTask> = new
+        .values()
+        .filter(|entry| !old.contains_key(&entry.task.id))
+        .filter(|entry| entry.parent.is_none_or(|parent| old.contains_key(&parent)))
+        .collect();
+    added.sort_by_key(|entry| (entry.parent, entry.position));
+    for entry in added {
+        changes.push(format!(
+            "Add task '{}'{}",
+            entry.task.name,
+            placement(new, entry.parent, "")
+        ));
+    }
+
+    let mut kept: Vec<(&FlatTask, &FlatTask)> = new
+        .values()
+        .filter_map(|entry| Some((old.get(&entry.task.id)?, entry)))
+        .collect();
+    kept.sort_by_key(|(_, entry)| (entry.parent, entry.position));
+    for (before, after) in kept {
+        let name = &after.task.name;
+        if before.parent != after.parent {
+            changes.push(format!(
+                "Move task '{}'{}",
+                name,
+                placement(new, after.parent, " to the top level")
+            ));
+        }
+        if before.task.name != after.task.name {
+            changes.push(format!("Rename task '{}' to '{}'", before.task.name, name));
+        }
+        if before.task.completed != after.task.completed {
+            let verb = if after.task.completed {
+                "Complete"
+            } else {
+                "Reopen"
+            };
+            changes.push(format!("{} task '{}'", verb, name));
+        }
+        let mut compared = before.task.clone();
+        compared.name = after.task.name.clone();
+        compared.completed = after.task.completed;
+        compared.completed_at = after.task.completed_at;
+        compared.expanded = after.task.expanded;
+        compared.modified = after.task.modified;
+        if compared != after.task {
+            changes.push(format!("Edit task '{}'", name));
+        }
+    }
+
+    let mut removed: Vec<&FlatTask> = old
+        .values()
+        .filter(|entry| !new.contains_key(&entry.task.id))
+        .filter(|entry| entry.parent.is_none_or(|parent| new.contains_key(&parent)))
+        .collect();
+    removed.sort_by_key(|entry| (entry.parent, entry.position));
+    for entry in removed {
+        changes.push(format!("Delete task '{}'", entry.task.name));
+    }
+    changes
+}
+
+/// Where a task sits, as the tail of a change description; `top_level` if it has no parent.
+fn placement(tree: &FlatTree, parent: Option<TaskId>, top_level: &str) -> String {
+    match parent.and_then(|parent| tree.get(&parent)) {
+        Some(parent) => format!(" under '{}'", parent.task.name),
+        None => top_level.to_string(),
+    }
+}
+
+/// A commit message for the described changes: the change itself when there is one, or a
+/// summary line followed by the full list.
+fn commit_message(changes: &[String]) -> String {
+    match changes {
+        [] => "Update task layout".to_string(),
+        [change] => change.clone(),
+        [first, rest @ ..] => format!(
+            "{} and {} more change(s)\n\n{}",
+            first,
+            rest.len(),
+            changes.join("\n")
+        ),
+    }
+}
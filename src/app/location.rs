@@ -0,0 +1,102 @@
+use super::App;
+use crate::app::task::{Task, TaskId};
+use crate::app::task_utils;
+use serde::{Deserialize, Serialize};
+
+/// Where a subtree sat in the tree before it was taken out, so it can be put back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Location {
+    /// The tasks it was nested under, from the top of the tree down.
+    pub ancestors: Vec<Ancestor>,
+    /// Its index among its siblings.
+    pub position: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ancestor {
+    pub id: TaskId,
+    pub name: String,
+}
+
+impl Location {
+    /// The location as a breadcrumb, e.g. `Work › Project`.
+    pub fn describe(&self) -> String {
+        if self.ancestors.is_empty() {
+            return "top level".to_string();
+        }
+        let names: Vec<&str> = self
+            .ancestors
+            .iter()
+            .map(|ancestor| ancestor.name.as_str())
+            .collect();
+        names.join(" › ")
+    }
+}
+
+impl App {
+    /// Removes the tasks and their subtrees from the tree, recording where each one was.
+    pub fn take_tasks(&mut self, ids: &[TaskId]) -> Vec<(Task, Location)> {
+        let paths = self.outermost_paths(ids);
+        let locations: Vec<Location> = paths
+            .iter()
+            .map(|path| {
+                let (position, parent_path) = path.split_last().unwrap();
+                let ancestors = (1..=parent_path.len())
+                    .filter_map(|len| task_utils::get_task(&self.tasks, &parent_path[..len]))
+                    .map(|ancestor| Ancestor {
+                        id: ancestor.id,
+                        name: ancestor.name.clone(),
+                    })
+                    .collect();
+                Location {
+                    ancestors,
+                    position: *position,
+                }
+            })
+            .collect();
+        self.stop_timer_within(&paths);
+        let removed = task_utils::remove_tasks(&mut self.tasks, &paths);
+        removed.into_iter().zip(locations).collect()
+    }
+
+    /// Puts a task back at `location`, or under its nearest ancestor that still exists, and
+    /// selects it. Returns a message saying where it ended up.
+    pub fn reinsert_task(&mut self, mut task: Task, location: &Location) -> String {
+        if task.any_in_subtree(&|task| self.index.contains_key(&task.id)) {
+            task = task.with_fresh_ids();
+        }
+        let restored_id = task.id;
+        let parent = location
+            .ancestors
+            .iter()
+            .rev()
+            .find(|ancestor| self.index.contains_key(&ancestor.id));
+        let is_original_parent = parent.map(|ancestor| ancestor.id)
+            == location.ancestors.last().map(|ancestor| ancestor.id);
+        let parent_path = parent
+            .and_then(|ancestor| self.path_of(ancestor.id))
+            .unwrap_or_default();
+        if let Some(siblings) = task_utils::get_children_mut(&mut self.tasks, &parent_path) {
+            let position = if is_original_parent {
+                location.position.min(siblings.len())
+            } else {
+                siblings.len()
+            };
+            siblings.insert(position, task);
+        }
+        if let Some(parent_task) = task_utils::get_task_mut(&mut self.tasks, &parent_path) {
+            parent_task.expanded = true;
+        }
+        let message = match (parent, is_original_parent) {
+            (_, true) => "Restored to its original location".to_string(),
+            (Some(ancestor), false) => format!(
+                "Original parent is gone, restored under '{}'",
+                ancestor.name
+            ),
+            (None, false) => "Original parent is gone, restored to the top level".to_string(),
+        };
+        self.reindex();
+        self.select_id_or_ancestor(restored_id);
+        message
+    }
+}
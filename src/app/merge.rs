@@ -0,0 +1,422 @@
+use crate::app::flat::{self, FlatTask, FlatTree};
+use crate::app::task::{Task, TaskId};
+use crate::storage;
+use color_eyre::eyre::{eyre, WrapErr};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Fields that only record bookkeeping or say how a task is shown or ordered. When both
+/// sides change them there is no conflict; this side's value wins. `expanded` always keeps
+/// this side's value, so merging never folds or unfolds the tree being looked at.
+const QUIET_FIELDS: [&str; 4] = ["created", "expanded", "modified", "position"];
+
+/// Which of the two modified trees a version comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The tree being merged into: this instance, or the current branch for git.
+    Ours,
+    /// The tree being merged in.
+    Theirs,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConflictKind {
+    /// Both sides changed these fields, to different values.
+    BothChanged { fields: Vec<String> },
+    /// One side deleted the task while the other changed it.
+    ChangedAndDeleted { deleted_by: Side },
+}
+
+/// A task the two sides disagree on. Until it is resolved the merged tree holds this side's
+/// value for every conflicting field, and keeps a task that was changed on one side even if
+/// the other deleted it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub id: TaskId,
+    pub kind: ConflictKind,
+    /// The task as this side left it; `None` if this side deleted it.
+    pub ours: Option<FlatTask>,
+    /// The task as the other side left it; `None` if that side deleted it.
+    pub theirs: Option<FlatTask>,
+}
+
+impl Conflict {
+    /// Name of the task, preferring this side's version.
+    pub fn name(&self) -> &str {
+        self.ours
+            .as_ref()
+            .or(self.theirs.as_ref())
+            .map_or("", |entry| entry.task.name.as_str())
+    }
+
+    /// One line saying what the sides disagree on.
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            ConflictKind::BothChanged { fields } if fields.iter().any(|field| field == "name") => {
+                let theirs = self.theirs.as_ref().map_or("", |entry| &entry.task.name);
+                format!("'{}' renamed differently: '{}' there", self.name(), theirs)
+            }
+            ConflictKind::BothChanged { fields } => {
+                format!(
+                    "'{}' changed on both sides: {}",
+                    self.name(),
+                    fields.join(", ")
+                )
+            }
+            ConflictKind::ChangedAndDeleted {
+                deleted_by: Side::Ours,
+            } => format!("'{}' deleted here but changed there", self.name()),
+            ConflictKind::ChangedAndDeleted {
+                deleted_by: Side::Theirs,
+            } => format!("'{}' changed here but deleted there", self.name()),
+        }
+    }
+}
+
+/// The result of merging two trees.
+#[derive(Debug, Default)]
+pub struct Merge {
+    pub tree: FlatTree,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Merges the changes `ours` and `theirs` each made to `base`, task by task and field by
+/// field. A field changed on one side only takes that side's value.
+pub fn merge(base: &FlatTree, ours: &FlatTree, theirs: &FlatTree) -> Merge {
+    let ids: HashSet<TaskId> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .copied()
+        .collect();
+    let mut merge = Merge::default();
+    for id in ids {
+        let (base, ours, theirs) = (base.get(&id), ours.get(&id), theirs.get(&id));
+        let merged = match (ours, theirs) {
+            (Some(ours), Some(theirs)) => {
+                let (mut entry, fields) = merge_task(base, ours, theirs);
+                entry.task.expanded = ours.task.expanded;
+                if !fields.is_empty() {
+                    merge.conflicts.push(Conflict {
+                        id,
+                        kind: ConflictKind::BothChanged { fields },
+                        ours: Some(ours.clone()),
+                        theirs: Some(theirs.clone()),
+                    });
+                }
+                Some(entry)
+            }
+            (Some(kept), None) | (None, Some(kept)) => {
+                let deleted_by = if ours.is_none() {
+                    Side::Ours
+                } else {
+                    Side::Theirs
+                };
+                match base {
+                    // Added on one side
+                    None => Some(kept.clone()),
+                    // Deleted on one side and left alone on the other
+                    Some(base) if !changed(base, kept) => None,
+                    Some(_) => {
+                        merge.conflicts.push(Conflict {
+                            id,
+                            kind: ConflictKind::ChangedAndDeleted { deleted_by },
+                            ours: ours.cloned(),
+                            theirs: theirs.cloned(),
+                        });
+                        Some(kept.clone())
+                    }
+                }
+            }
+            (None, None) => None,
+        };
+        if let Some(entry) = merged {
+            merge.tree.insert(id, entry);
+        }
+    }
+    merge
+        .conflicts
+        .sort_by(|a, b| a.name().cmp(b.name()).then(a.id.cmp(&b.id)));
+    merge
+}
+
+/// Merges one task field by field, returning it with the fields both sides changed
+/// differently. Those keep this side's value.
+fn merge_task(
+    base: Option<&FlatTask>,
+    ours: &FlatTask,
+    theirs: &FlatTask,
+) -> (FlatTask, Vec<String>) {
+    if ours == theirs || base == Some(theirs) {
+        return (ours.clone(), Vec::new());
+    }
+    if base == Some(ours) {
+        return (theirs.clone(), Vec::new());
+    }
+    let base = base.map(fields).unwrap_or_default();
+    let ours_fields = fields(ours);
+    let theirs_fields = fields(theirs);
+    let keys: HashSet<&String> = base
+        .keys()
+        .chain(ours_fields.keys())
+        .chain(theirs_fields.keys())
+        .collect();
+    let mut merged = ours_fields.clone();
+    let mut conflicting = Vec::new();
+    for key in keys {
+        let base_value = base.get(key);
+        let ours_value = ours_fields.get(key);
+        let theirs_value = theirs_fields.get(key);
+        if ours_value == theirs_value || base_value == theirs_value {
+            continue;
+        }
+        if ours_value == base_value {
+            match theirs_value {
+                Some(value) => merged.insert(key.clone(), value.clone()),
+                None => merged.remove(key),
+            };
+        } else if !QUIET_FIELDS.contains(&key.as_str()) {
+            conflicting.push(key.clone());
+        }
+    }
+    conflicting.sort();
+    let merged = serde_json::from_value(Value::Object(merged)).unwrap_or_else(|_| ours.clone());
+    (merged, conflicting)
+}
+
+/// Whether `entry` differs from `base` in more than how it is shown or ordered.
+fn changed(base: &FlatTask, entry: &FlatTask) -> bool {
+    let mut base = fields(base);
+    let mut entry = fields(entry);
+    for field in QUIET_FIELDS {
+        base.remove(field);
+        entry.remove(field);
+    }
+    base != entry
+}
+
+fn fields(entry: &FlatTask) -> Map<String, Value> {
+    match serde_json::to_value(entry) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    }
+}
+
+/// Settles a conflict in a merged tree by taking `side`'s version: its values for the
+/// conflicting fields, or whether the task exists at all.
+pub fn resolve(tree: &mut FlatTree, conflict: &Conflict, side: Side) {
+    let chosen = match side {
+        Side::Ours => conflict.ours.as_ref(),
+        Side::Theirs => conflict.theirs.as_ref(),
+    };
+    let Some(chosen) = chosen else {
+        tree.remove(&conflict.id);
+        return;
+    };
+    let ConflictKind::BothChanged {
+        fields: conflicting,
+    } = &conflict.kind
+    else {
+        tree.insert(conflict.id, chosen.clone());
+        return;
+    };
+    let Some(current) = tree.get(&conflict.id) else {
+        return;
+    };
+    let mut merged = fields(current);
+    let chosen = fields(chosen);
+    for field in conflicting {
+        if let Some(value) = chosen.get(field) {
+            merged.insert(field.clone(), value.clone());
+        }
+    }
+    if let Ok(entry) = serde_json::from_value(Value::Object(merged)) {
+        tree.insert(conflict.id, entry);
+    }
+}
+
+/// The `merge` command, for use as a git merge driver on the data file:
+///
+/// ```text
+/// # .vinegar-git/info/attributes
+/// tasks.json merge=vinegar
+/// # .vinegar-git/config
+/// [merge "vinegar"]
+///     driver = vinegar merge %O %A %B
+/// ```
+///
+/// Run git on the data file's repository with `git --git-dir=.vinegar-git`.
+///
+/// Writes the merged tree over `ours` and returns `false` if conflicts are left, which git
+/// then reports. Opening vinegar on the conflicted file lets them be resolved one by one;
+/// stage and commit the file afterwards to finish the merge.
+pub fn merge_command(args: &[String]) -> color_eyre::Result<bool> {
+    let [base, ours, theirs] = args else {
+        return Err(eyre!("Usage: vinegar merge <base> <ours> <theirs>"));
+    };
+    let base = read_tree(Path::new(base))?;
+    let ours_path = Path::new(ours);
+    let ours = read_tree(ours_path)?;
+    let theirs = read_tree(Path::new(theirs))?;
+    let merge = merge(
+        &flat::flatten(&base),
+        &flat::flatten(&ours),
+        &flat::flatten(&theirs),
+    );
+    storage::write_json(ours_path, &flat::unflatten(&merge.tree))?;
+    for conflict in &merge.conflicts {
+        eprintln!("conflict: {}", conflict.describe());
+    }
+    Ok(merge.conflicts.is_empty())
+}
+
+/// Reads a version of the data file. Git passes an empty file when there is no common
+/// ancestor.
+fn read_tree(path: &Path) -> color_eyre::Result<Vec<Task>> {
+    let data =
+        fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    if data.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&data).wrap_err_with(|| format!("Failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Local};
+
+    fn tree(tasks: &[Task]) -> FlatTree {
+        flat::flatten(tasks)
+    }
+
+    fn edited(tree: &FlatTree, id: TaskId, edit: impl FnOnce(&mut FlatTask)) -> FlatTree {
+        let mut tree = tree.clone();
+        edit(tree.get_mut(&id).unwrap());
+        tree
+    }
+
+    #[test]
+    fn takes_a_change_made_on_one_side() {
+        let task = Task::new("Write report".to_string());
+        let base = tree(std::slice::from_ref(&task));
+        let theirs = edited(&base, task.id, |entry| {
+            entry.task.name = "Write summary".into()
+        });
+
+        let merged = merge(&base, &base, &theirs);
+
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.tree[&task.id].task.name, "Write summary");
+    }
+
+    #[test]
+    fn merges_different_fields_changed_on_each_side() {
+        let task = Task::new("Write report".to_string());
+        let base = tree(std::slice::from_ref(&task));
+        let ours = edited(&base, task.id, |entry| {
+            entry.task.tags = vec!["work".into()]
+        });
+        let theirs = edited(&base, task.id, |entry| entry.task.completed = true);
+
+        let merged = merge(&base, &ours, &theirs);
+
+        assert!(merged.conflicts.is_empty());
+        let entry = &merged.tree[&task.id];
+        assert_eq!(entry.task.tags, vec!["work".to_string()]);
+        assert!(entry.task.completed);
+    }
+
+    #[test]
+    fn keeps_ours_where_both_changed_the_same_field() {
+        let task = Task::new("Write report".to_string());
+        let base = tree(std::slice::from_ref(&task));
+        let ours = edited(&base, task.id, |entry| entry.task.name = "Here".into());
+        let theirs = edited(&base, task.id, |entry| entry.task.name = "There".into());
+
+        let merged = merge(&base, &ours, &theirs);
+
+        assert_eq!(merged.tree[&task.id].task.name, "Here");
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(
+            merged.conflicts[0].kind,
+            ConflictKind::BothChanged {
+                fields: vec!["name".to_string()]
+            }
+        );
+
+        let mut resolved = merged.tree.clone();
+        resolve(&mut resolved, &merged.conflicts[0], Side::Theirs);
+        assert_eq!(resolved[&task.id].task.name, "There");
+    }
+
+    #[test]
+    fn keeps_a_task_edited_on_one_side_and_deleted_on_the_other() {
+        let task = Task::new("Write report".to_string());
+        let base = tree(std::slice::from_ref(&task));
+        let ours = edited(&base, task.id, |entry| entry.task.name = "Edited".into());
+        let theirs = FlatTree::new();
+
+        let merged = merge(&base, &ours, &theirs);
+
+        assert_eq!(merged.tree[&task.id].task.name, "Edited");
+        assert_eq!(
+            merged.conflicts[0].kind,
+            ConflictKind::ChangedAndDeleted {
+                deleted_by: Side::Theirs
+            }
+        );
+    }
+
+    #[test]
+    fn drops_a_task_deleted_on_one_side_and_left_alone_on_the_other() {
+        let task = Task::new("Write report".to_string());
+        let base = tree(std::slice::from_ref(&task));
+
+        let merged = merge(&base, &FlatTree::new(), &base);
+
+        assert!(merged.conflicts.is_empty());
+        assert!(merged.tree.is_empty());
+    }
+
+    #[test]
+    fn quiet_fields_never_conflict() {
+        let mut first = Task::new("First".to_string());
+        let second = Task::new("Second".to_string());
+        first.subtasks.push(Task::new("Child".to_string()));
+        let base = tree(&[first.clone(), second.clone()]);
+        let later = Local::now() + Duration::hours(1);
+        let ours = edited(&base, first.id, |entry| {
+            entry.task.expanded = true;
+            entry.task.modified = later;
+            entry.position = 1;
+        });
+        let theirs = edited(&base, first.id, |entry| {
+            entry.task.expanded = false;
+            entry.task.modified = later + Duration::hours(1);
+            entry.position = 2;
+        });
+
+        let merged = merge(&base, &ours, &theirs);
+
+        assert!(merged.conflicts.is_empty());
+        let entry = &merged.tree[&first.id];
+        assert!(entry.task.expanded);
+        assert_eq!(entry.task.modified, later);
+        assert_eq!(entry.position, 1);
+    }
+
+    #[test]
+    fn deleting_a_task_untouched_except_for_quiet_fields_is_no_conflict() {
+        let task = Task::new("Write report".to_string());
+        let base = tree(std::slice::from_ref(&task));
+        let ours = edited(&base, task.id, |entry| entry.task.expanded = true);
+
+        let merged = merge(&base, &ours, &FlatTree::new());
+
+        assert!(merged.conflicts.is_empty());
+        assert!(merged.tree.is_empty());
+    }
+}
@@ -1,15 +1,53 @@
 pub mod actions;
+pub mod archive;
+pub mod autosave;
+pub mod backups;
+pub mod bulk;
+pub mod calendar;
+pub mod clipboard;
+pub mod conflicts;
+pub mod dependencies;
+pub mod export;
+pub mod flat;
+pub mod history;
+pub mod location;
+pub mod merge;
+pub mod parse;
+pub mod pomodoro;
+pub mod recurrence;
+pub mod remote;
 pub mod state;
 pub mod task;
 pub mod task_utils;
+pub mod timer;
+pub mod trash;
+pub mod view;
+pub mod watch;
 
-use crate::config::Config;
+use crate::backup::{self, Backup};
+use crate::caldav::SyncState;
+use crate::config::{Config, GroupKey, SortKey};
+use crate::control::Notification;
 use crate::event::{AppEvent, Event, EventHandler};
+use crate::git::{Commit, GitStore};
+use crate::journal::{Journal, JournalEntry};
+use crate::server::SharedTasks;
+use crate::storage::FileVersion;
 use crate::ui;
+use archive::ArchivedTask;
+use chrono::{DateTime, Local};
+use flat::FlatTree;
+use merge::Conflict;
+use pomodoro::Pomodoro;
 use ratatui::widgets::ListState;
 use ratatui::DefaultTerminal;
 use state::AppMode;
-use task::Task;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use task::{Task, TaskId};
+use timer::ActiveTimer;
+use tokio::sync::broadcast;
+use trash::TrashedTask;
 
 /// Application.
 #[derive(Debug)]
@@ -19,122 +57,389 @@ pub struct App {
     /// Event handler.
     pub events: EventHandler,
     pub tasks: Vec<Task>,
+    /// Path of every task in `tasks` by ID, rebuilt whenever the tree changes shape.
+    pub index: HashMap<TaskId, Vec<usize>>,
     pub input: String,
     pub mode: AppMode,
     pub task_list_state: ListState,
+    /// Task the list selection follows when rows move around it.
+    pub selected_task: Option<TaskId>,
     pub config: Config,
+    /// Tasks marked for a bulk action.
+    pub marked: HashSet<TaskId>,
+    /// One-line feedback shown in the input block until the next key press.
+    pub status_message: Option<String>,
+    /// Subtrees that were yanked or cut, ready to be pasted.
+    pub clipboard: Vec<Task>,
+    /// Active sort key for the task list; `None` shows the stored order.
+    pub sort: Option<SortKey>,
+    /// Active grouping view; `None` shows the task tree.
+    pub group: Option<GroupKey>,
+    /// Task the view is zoomed into; `None` when the whole tree is shown.
+    pub zoom: Option<TaskId>,
+    /// Whether the detail pane for the selected task is shown beside the list.
+    pub show_details: bool,
+    /// Whether the list shows only the tasks that can be worked on now.
+    pub actionable_only: bool,
+    /// The running time tracker, if any.
+    pub timer: Option<ActiveTimer>,
+    /// The running Pomodoro session, if any.
+    pub pomodoro: Option<Pomodoro>,
+    /// Completed subtrees moved out of the tree, oldest first.
+    pub archive: Vec<ArchivedTask>,
+    /// Selection in the archive browser.
+    pub archive_list_state: ListState,
+    /// Deleted subtrees waiting to be restored or purged, oldest first.
+    pub trash: Vec<TrashedTask>,
+    /// Selection in the trash browser.
+    pub trash_list_state: ListState,
+    /// Write-ahead log of changes since the last snapshot.
+    pub journal: Journal,
+    /// The tree as of the last journal entry, to work out what changed since.
+    pub saved_tree: FlatTree,
+    /// When the latest change that is not in the data file yet was made.
+    pub unsaved_since: Option<Instant>,
+    /// The data file as it was last read or written here.
+    pub disk_version: FileVersion,
+    /// The tree stored in `disk_version`, the common base when merging in other instances' changes.
+    pub disk_tree: FlatTree,
+    /// When the data file was last checked for changes made elsewhere.
+    pub last_disk_check: Instant,
+    /// Journal entries left behind by an unclean shutdown, waiting to be replayed.
+    pub recovered: Vec<JournalEntry>,
+    /// When the newest backup was taken.
+    pub last_backup: Option<DateTime<Local>>,
+    /// Backups listed in the backup browser, newest first.
+    pub backups: Vec<Backup>,
+    /// Selection in the backup browser.
+    pub backup_list_state: ListState,
+    /// The task tree saved in the selected backup.
+    pub backup_preview: Vec<Task>,
+    /// Repository the data file is committed to, when git versioning is enabled.
+    pub git: Option<GitStore>,
+    /// The tree as of the last commit, to describe what the next one changes.
+    pub committed_tree: FlatTree,
+    /// When the latest change that is not committed yet was made.
+    pub uncommitted_since: Option<Instant>,
+    /// Commits listed in the history browser, newest first.
+    pub history: Vec<Commit>,
+    /// Selection in the history browser.
+    pub history_list_state: ListState,
+    /// What the selected commit changed, one line per change.
+    pub history_changes: Vec<String>,
+    /// Merge conflicts waiting for a side to be chosen.
+    pub conflicts: Vec<Conflict>,
+    /// Selection in the conflict screen.
+    pub conflict_list_state: ListState,
+    /// The tree as seen by the HTTP API and control socket, when either is running.
+    pub shared_tasks: Option<SharedTasks>,
+    /// Channel to the control socket's subscribers, when it is running.
+    pub notifications: Option<broadcast::Sender<Notification>>,
+    /// Task subscribers were last told is selected.
+    pub notified_selection: Option<TaskId>,
+    /// What each task was last synced with the CalDAV collection as.
+    pub calendar_state: SyncState,
+    /// Whether a calendar sync is running in the background.
+    pub calendar_syncing: bool,
+    /// When the latest calendar sync was started.
+    pub last_calendar_sync: Option<Instant>,
 }
 
 impl App {
     /// Constructs a new instance of [`App`].
-    pub fn new(config: Config) -> Self {
-        Self {
+    pub fn new(
+        config: Config,
+        tasks: Vec<Task>,
+        disk_version: FileVersion,
+        archive: Vec<ArchivedTask>,
+        trash: Vec<TrashedTask>,
+    ) -> Self {
+        let (sort, group) = (config.view.sort, config.view.group);
+        let index = task_utils::build_index(&tasks);
+        let journal = Journal::new(&config);
+        let saved_tree = flat::flatten(&tasks);
+        let committed_tree = saved_tree.clone();
+        let disk_tree = saved_tree.clone();
+        let last_backup = backup::list_backups(&config)
+            .ok()
+            .and_then(|backups| backups.first().map(|backup| backup.created));
+        let mut app = Self {
             running: true,
             events: EventHandler::new(),
-            tasks: Vec::new(),
+            tasks,
+            index,
             input: String::new(),
             mode: AppMode::Normal,
             task_list_state: ListState::default(),
+            selected_task: None,
             config,
-        }
+            marked: HashSet::new(),
+            status_message: None,
+            clipboard: Vec::new(),
+            sort,
+            group,
+            zoom: None,
+            show_details: false,
+            actionable_only: false,
+            timer: None,
+            pomodoro: None,
+            archive,
+            archive_list_state: ListState::default(),
+            trash,
+            trash_list_state: ListState::default(),
+            journal,
+            saved_tree,
+            unsaved_since: None,
+            disk_version,
+            disk_tree,
+            last_disk_check: Instant::now(),
+            recovered: Vec::new(),
+            last_backup,
+            backups: Vec::new(),
+            backup_list_state: ListState::default(),
+            backup_preview: Vec::new(),
+            git: None,
+            committed_tree,
+            uncommitted_since: None,
+            history: Vec::new(),
+            history_list_state: ListState::default(),
+            history_changes: Vec::new(),
+            conflicts: Vec::new(),
+            conflict_list_state: ListState::default(),
+            shared_tasks: None,
+            notifications: None,
+            notified_selection: None,
+            calendar_state: SyncState::new(),
+            calendar_syncing: false,
+            last_calendar_sync: None,
+        };
+        app.purge_expired_trash();
+        app.open_git_store();
+        app.start_remote();
+        app.open_calendar();
+        app
     }
 
     /// Run the application's main loop.
     pub async fn run(mut self, mut terminal: DefaultTerminal) -> color_eyre::Result<()> {
         while self.running {
+            self.notify_selection();
             terminal.draw(|frame| ui::ui::ui(frame, &mut self))?;
             match self.events.next().await? {
                 Event::Tick => self.tick(),
                 Event::Crossterm(event) => {
                     if let crossterm::event::Event::Key(key_event) = event {
-                        actions::handle_key_events(key_event, &mut self)?
+                        actions::handle_key_events(key_event, &mut self)?;
+                        self.record_changes();
                     }
                 }
-                Event::App(app_event) => match app_event {
-                    AppEvent::Quit => self.quit(),
-                    AppEvent::AddTask => {
-                        if let Some(selected_index) = self.task_list_state.selected() {
-                            let tasks_to_display = self.get_tasks_to_display();
-                            if let Some(selected_task_info) = tasks_to_display.get(selected_index) {
-                                let task_path = &selected_task_info.1;
-                                if let Some(task) =
-                                    task_utils::get_task_mut(&mut self.tasks, task_path)
-                                {
-                                    task.subtasks
-                                        .push(Task::new(self.input.drain(..).collect()));
-                                    task.expanded = true;
+                Event::App(app_event) => {
+                    match app_event {
+                        AppEvent::Quit => self.quit(),
+                        AppEvent::AddTask { id, parent, input } => {
+                            let mut new_task = Task::from_input(&input);
+                            new_task.id = id;
+                            match parent {
+                                Some(parent_id) => {
+                                    if let Some(task) = self.task_mut(parent_id) {
+                                        task.subtasks.push(new_task);
+                                        task.expanded = true;
+                                    }
                                 }
+                                None => self.tasks.push(new_task),
                             }
-                        } else {
-                            self.tasks.push(Task::new(self.input.drain(..).collect()));
                         }
-                        self.mode = AppMode::Normal;
-                    }
-                    AppEvent::UpdateTask => {
-                        if let AppMode::EditingTask { path } = &self.mode {
-                            if let Some(task) = task_utils::get_task_mut(&mut self.tasks, path) {
-                                task.name = self.input.drain(..).collect();
+                        AppEvent::UpdateTask { id, input } => {
+                            if let Some(task) = self.task_mut(id) {
+                                task.set_from_input(&input);
                             }
                         }
-                        self.mode = AppMode::Normal;
-                    }
-                    AppEvent::DeleteTask => {
-                        if let Some(selected_index) = self.task_list_state.selected() {
-                            let tasks_to_display = self.get_tasks_to_display();
-                            if let Some(selected_task_info) =
-                                tasks_to_display.get(selected_index).cloned()
-                            {
-                                let task_path = selected_task_info.1;
-
-                                if task_path.len() > 1 {
-                                    let parent_path = &task_path[..task_path.len() - 1];
-                                    let task_index = *task_path.last().unwrap();
-                                    if let Some(parent_task) =
-                                        task_utils::get_task_mut(&mut self.tasks, parent_path)
-                                    {
-                                        parent_task.subtasks.remove(task_index);
-                                    }
-                                } else {
-                                    let task_index = task_path[0];
-                                    self.tasks.remove(task_index);
-                                }
-
-                                let task_count = self.get_tasks_to_display().len();
-                                if task_count == 0 {
-                                    self.task_list_state.select(None);
-                                } else if selected_index >= task_count {
-                                    self.task_list_state.select(Some(task_count - 1));
-                                }
-                            }
+                        AppEvent::DeleteTasks { ids } => self.delete_tasks(&ids),
+                        AppEvent::ToggleComplete { ids, force } => {
+                            self.toggle_complete(&ids, force)
                         }
+                        AppEvent::MoveTasks { ids, parent } => self.move_tasks(&ids, parent),
+                        AppEvent::TagTasks { ids, tag } => self.tag_tasks(&ids, &tag),
+                        AppEvent::ExportTasks { ids } => self.export_tasks(&ids),
+                        AppEvent::YankTasks { ids } => self.yank_tasks(&ids),
+                        AppEvent::CutTasks { ids } => self.cut_tasks(&ids),
+                        AppEvent::PasteTasks { target, as_child } => self.paste(target, as_child),
+                        AppEvent::SortSiblings { id } => self.sort_siblings(id),
+                        AppEvent::BlockTask { id, blockers } => self.block_task(id, &blockers),
+                        AppEvent::UnblockTask { id } => self.unblock_task(id),
+                        AppEvent::ToggleTimer { id } => self.toggle_timer(id),
+                        AppEvent::TogglePomodoro { id } => self.toggle_pomodoro(id),
+                        AppEvent::ArchiveTasks { ids } => self.archive_tasks(&ids),
+                        AppEvent::ArchiveCompleted { older_than_days } => {
+                            self.archive_completed(older_than_days)
+                        }
+                        AppEvent::RestoreArchived { id } => self.restore_archived(id),
+                        AppEvent::RestoreTrashed { id } => self.restore_trashed(id),
+                        AppEvent::PurgeTrashed { ids } => self.purge_trashed(&ids),
+                        AppEvent::ReplayJournal => self.replay_journal(),
+                        AppEvent::RestoreBackup { path } => self.restore_backup(&path),
+                        AppEvent::RollBack { hash } => self.roll_back(&hash),
+                        AppEvent::SelectTask { id } => self.select_id_or_ancestor(id),
+                        AppEvent::ResolveConflict { id, side } => self.resolve_conflict(id, side),
+                        AppEvent::CalendarSynced { result } => self.finish_calendar_sync(result),
                     }
-                },
+                    // Any event may have reshaped the tree
+                    self.reindex();
+                    self.sync_selection();
+                    self.record_changes();
+                }
             }
         }
+        // Log the time spent so far rather than losing it
+        self.stop_timer();
+        self.stop_remote();
+        self.snapshot()?;
+        self.journal.close()?;
+        self.commit_changes();
         Ok(())
     }
 
     /// Handles the tick event of the terminal.
-    pub fn tick(&self) {}
+    pub fn tick(&mut self) {
+        self.update_timer();
+        if self.update_pomodoro() {
+            self.record_changes();
+        }
+        self.watch_data_file();
+        self.save_if_settled();
+        self.commit_if_settled();
+        self.sync_calendar_if_due();
+    }
 
     /// Set running to false to quit the application.
     pub fn quit(&mut self) {
         self.running = false;
     }
 
-    pub fn get_tasks_to_display(&self) -> Vec<(String, Vec<usize>)> {
+    /// Rebuilds the ID index after the tree changed shape, forgetting tasks that are gone.
+    pub fn reindex(&mut self) {
+        self.index = task_utils::build_index(&self.tasks);
+        let index = &self.index;
+        self.marked.retain(|id| index.contains_key(id));
+        if self.zoom.is_some_and(|id| !index.contains_key(&id)) {
+            self.zoom = None;
+        }
+        // Removing a task stops its timer first; this catches tasks that vanished from
+        // outside, such as in a reload, where there is nowhere left to log the time
+        if self
+            .timer
+            .as_ref()
+            .is_some_and(|timer| !index.contains_key(&timer.task))
+        {
+            self.timer = None;
+        }
+        if self
+            .pomodoro
+            .as_ref()
+            .is_some_and(|pomodoro| !index.contains_key(&pomodoro.task))
+        {
+            self.pomodoro = None;
+        }
+    }
+
+    /// Path of the task with `id`, if it is still in the tree.
+    pub fn path_of(&self, id: TaskId) -> Option<Vec<usize>> {
+        self.index.get(&id).cloned()
+    }
+
+    /// Finds a task by ID.
+    pub fn task(&self, id: TaskId) -> Option<&Task> {
+        task_utils::get_task(&self.tasks, self.index.get(&id)?)
+    }
+
+    /// Finds a task by ID for editing.
+    pub fn task_mut(&mut self, id: TaskId) -> Option<&mut Task> {
+        let path = self.index.get(&id)?;
+        task_utils::get_task_mut(&mut self.tasks, path)
+    }
+
+    /// ID of the selected task, or `None` if nothing or a group header is selected.
+    pub fn selected_id(&self) -> Option<TaskId> {
+        let selected_index = self.task_list_state.selected()?;
+        self.get_tasks_to_display().get(selected_index)?.1
+    }
+
+    /// Selects the row at `index` and remembers which task it shows.
+    pub fn select_index(&mut self, index: Option<usize>) {
+        self.task_list_state.select(index);
+        self.selected_task = self.selected_id();
+    }
+
+    /// Selects the row showing the task with `id`, or clears the selection if it is not visible.
+    pub fn select_id(&mut self, id: TaskId) {
+        let index = self
+            .get_tasks_to_display()
+            .iter()
+            .position(|(_, row_id)| *row_id == Some(id));
+        self.select_index(index);
+    }
+
+    /// Selects the task with `id`, or its nearest visible ancestor if it is hidden inside a
+    /// collapsed task.
+    pub fn select_id_or_ancestor(&mut self, id: TaskId) {
+        let Some(path) = self.path_of(id) else {
+            return;
+        };
+        let tasks_to_display = self.get_tasks_to_display();
+        let index = (1..=path.len()).rev().find_map(|len| {
+            let ancestor_id = task_utils::get_task(&self.tasks, &path[..len])?.id;
+            tasks_to_display
+                .iter()
+                .position(|(_, row_id)| *row_id == Some(ancestor_id))
+        });
+        self.select_index(index);
+    }
+
+    /// Keeps the selection inside the list after tasks were removed from it.
+    pub fn clamp_selection(&mut self) {
+        let task_count = self.get_tasks_to_display().len();
+        if task_count == 0 {
+            self.select_index(None);
+        } else if let Some(selected_index) = self.task_list_state.selected() {
+            self.select_index(Some(selected_index.min(task_count - 1)));
+        }
+    }
+
+    /// Moves the selection back onto the task it was following after the rows shifted around
+    /// it. If that task is gone the selection stays at the same position in the list.
+    pub fn sync_selection(&mut self) {
+        if let Some(id) = self.selected_task {
+            if self.selected_id() == Some(id) {
+                return;
+            }
+            if self.index.contains_key(&id) {
+                self.select_id_or_ancestor(id);
+                return;
+            }
+        }
+        self.clamp_selection();
+    }
+
+    /// Rows of the task list as display text and task ID. Group header rows have no ID.
+    pub fn get_tasks_to_display(&self) -> Vec<(String, Option<TaskId>)> {
+        if self.actionable_only {
+            return self.get_actionable_tasks_to_display();
+        }
+        if let Some(group) = self.group {
+            return self.get_grouped_tasks_to_display(group);
+        }
+        let root_tasks = self.view_root();
         let mut display_tasks = Vec::new();
-        for (i, task) in self.tasks.iter().enumerate() {
-            self.add_task_to_display(&mut display_tasks, task, vec![i], 0);
+        for i in self.sorted_indices(root_tasks) {
+            self.add_task_to_display(&mut display_tasks, &root_tasks[i], 0);
         }
         display_tasks
     }
 
     fn add_task_to_display(
         &self,
-        display_tasks: &mut Vec<(String, Vec<usize>)>,
+        display_tasks: &mut Vec<(String, Option<TaskId>)>,
         task: &Task,
-        path: Vec<usize>,
         depth: usize,
     ) {
         let prefix = " ".repeat(depth * 2);
@@ -145,16 +450,68 @@ impl App {
         } else {
             &self.config.theme.icons.collapsed
         };
+        let blocked = if self.is_blocked(task) {
+            self.config.theme.icons.blocked.as_str()
+        } else {
+            ""
+        };
+        let timing = if self
+            .timer
+            .as_ref()
+            .is_some_and(|timer| timer.task == task.id)
+        {
+            self.config.theme.icons.timer.as_str()
+        } else {
+            ""
+        };
         // Use the icon and add a space after it
-        let display_name = format!("{}{}{}", prefix, icon, task.name);
-        display_tasks.push((display_name, path.clone()));
+        let display_name = format!(
+            "{}{}{}{}{}{}",
+            prefix,
+            icon,
+            timing,
+            blocked,
+            task.name,
+            self.progress_label(task)
+        );
+        display_tasks.push((display_name, Some(task.id)));
 
         if task.expanded {
-            for (i, subtask) in task.subtasks.iter().enumerate() {
-                let mut sub_path = path.clone();
-                sub_path.push(i);
-                self.add_task_to_display(display_tasks, subtask, sub_path, depth + 1);
+            for i in self.sorted_indices(&task.subtasks) {
+                self.add_task_to_display(display_tasks, &task.subtasks[i], depth + 1);
             }
         }
     }
 }
+
+/// Keeps a list selection inside a list that now has `len` rows.
+pub(crate) fn clamp_list_state(state: &mut ListState, len: usize) {
+    match state.selected() {
+        _ if len == 0 => state.select(None),
+        Some(selected) if selected >= len => state.select(Some(len - 1)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+impl App {
+    /// An app over `tasks` whose files all live in a fresh temporary directory.
+    pub fn for_tests(tasks: Vec<Task>) -> Self {
+        let dir = std::env::temp_dir().join(format!("vinegar-app-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).display().to_string();
+        let mut config = Config::default();
+        config.storage.path = path("tasks.json");
+        config.archive.path = path("archive.json");
+        config.trash.path = path("trash.json");
+        config.backup.dir = path("backups");
+        config.clipboard.osc52 = false;
+        App::new(
+            config,
+            tasks,
+            FileVersion::default(),
+            Vec::new(),
+            Vec::new(),
+        )
+    }
+}
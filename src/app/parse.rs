@@ -0,0 +1,145 @@
+use crate::app::recurrence::Recurrence;
+use crate::app::task::{Priority, Task};
+use chrono::{Days, Local, NaiveDate};
+
+/// A task name with the attributes typed inline after it, e.g.
+/// `Buy milk #shop !high due:tomorrow rec:weekly:sat`.
+#[derive(Debug, Default)]
+pub struct TaskInput {
+    pub name: String,
+    pub tags: Vec<String>,
+    pub priority: Option<Priority>,
+    pub due: Option<NaiveDate>,
+    pub recurrence: Option<Recurrence>,
+}
+
+/// Splits task input into its name and inline attributes. Words that do not parse as an
+/// attribute are kept as part of the name, and so is a word after a backslash, which lets a
+/// name contain `\#123` or `\due:friday`.
+pub fn parse_task_input(input: &str, today: NaiveDate) -> TaskInput {
+    let mut parsed = TaskInput::default();
+    let mut name_words = Vec::new();
+    for word in input.split_whitespace() {
+        if let Some(literal) = word.strip_prefix('\\') {
+            name_words.push(literal);
+        } else if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+            if !parsed.tags.iter().any(|existing| existing == tag) {
+                parsed.tags.push(tag.to_string());
+            }
+        } else if let Some(priority) = word.strip_prefix('!').and_then(Priority::parse) {
+            parsed.priority = Some(priority);
+        } else if let Some(due) = word
+            .strip_prefix("due:")
+            .and_then(|date| parse_date(date, today))
+        {
+            parsed.due = Some(due);
+        } else if let Some(recurrence) = word.strip_prefix("rec:").and_then(Recurrence::parse) {
+            parsed.recurrence = Some(recurrence);
+        } else {
+            name_words.push(word);
+        }
+    }
+    parsed.name = name_words.join(" ");
+    parsed
+}
+
+/// Parses `YYYY-MM-DD`, `today`, `tomorrow` or a relative `+Nd`.
+pub fn parse_date(date: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match date.to_lowercase().as_str() {
+        "today" => Some(today),
+        "tomorrow" => today.checked_add_days(Days::new(1)),
+        relative if relative.starts_with('+') && relative.ends_with('d') => {
+            let days = relative[1..relative.len() - 1].parse().ok()?;
+            today.checked_add_days(Days::new(days))
+        }
+        absolute => NaiveDate::parse_from_str(absolute, "%Y-%m-%d").ok(),
+    }
+}
+
+/// Renders a task back into the inline syntax [`parse_task_input`] understands, escaping
+/// the words of its name that would otherwise be read as attributes.
+pub fn format_task_input(task: &Task) -> String {
+    let today = Local::now().date_naive();
+    let words: Vec<String> = task
+        .name
+        .split_whitespace()
+        .map(|word| {
+            if word.starts_with('\\') || parse_task_input(word, today).name.is_empty() {
+                format!("\\{}", word)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect();
+    let mut input = words.join(" ");
+    for tag in &task.tags {
+        input.push_str(&format!(" #{}", tag));
+    }
+    if let Some(priority) = task.priority {
+        input.push_str(&format!(" !{}", priority.as_str()));
+    }
+    if let Some(due) = task.due {
+        input.push_str(&format!(" due:{}", due.format("%Y-%m-%d")));
+    }
+    if let Some(recurrence) = &task.recurrence {
+        input.push_str(&format!(" rec:{}", recurrence.as_input()));
+    }
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Weekday;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, 10).unwrap()
+    }
+
+    #[test]
+    fn reads_attributes_and_keeps_the_other_words_as_the_name() {
+        let parsed = parse_task_input("Buy milk #shop !high due:tomorrow rec:weekly:sat", today());
+        assert_eq!(parsed.name, "Buy milk");
+        assert_eq!(parsed.tags, vec!["shop".to_string()]);
+        assert_eq!(parsed.priority, Some(Priority::High));
+        assert_eq!(parsed.due, NaiveDate::from_ymd_opt(2024, 5, 11));
+        assert_eq!(
+            parsed.recurrence,
+            Some(Recurrence::Weekly {
+                weekdays: vec![Weekday::Sat]
+            })
+        );
+    }
+
+    #[test]
+    fn escaped_words_stay_in_the_name() {
+        let parsed = parse_task_input(r"Fix bug \#123 #work \due:friday \\server", today());
+        assert_eq!(parsed.name, r"Fix bug #123 due:friday \server");
+        assert_eq!(parsed.tags, vec!["work".to_string()]);
+        assert_eq!(parsed.due, None);
+    }
+
+    #[test]
+    fn formatted_input_parses_back_to_the_same_task() {
+        let mut task = Task::new(r"Fix bug #123 !high due:today rec:daily \n".to_string());
+        task.tags = vec!["work".to_string(), "urgent".to_string()];
+        task.priority = Some(Priority::Medium);
+        task.due = NaiveDate::from_ymd_opt(2024, 6, 1);
+        task.recurrence = Some(Recurrence::Monthly { day: Some(31) });
+
+        let input = format_task_input(&task);
+        let parsed = parse_task_input(&input, today());
+
+        assert_eq!(parsed.name, task.name);
+        assert_eq!(parsed.tags, task.tags);
+        assert_eq!(parsed.priority, task.priority);
+        assert_eq!(parsed.due, task.due);
+        assert_eq!(parsed.recurrence, task.recurrence);
+    }
+
+    #[test]
+    fn plain_names_are_formatted_unchanged() {
+        let task = Task::new("Call #  the bank! at 5".to_string());
+        assert_eq!(format_task_input(&task), "Call # the bank! at 5");
+    }
+}
@@ -0,0 +1,140 @@
+use super::App;
+use crate::app::task::TaskId;
+use crate::event::TICK_FPS;
+use chrono::{DateTime, Local, TimeDelta};
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PomodoroPhase {
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+impl PomodoroPhase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PomodoroPhase::Work => "Work",
+            PomodoroPhase::ShortBreak => "Short break",
+            PomodoroPhase::LongBreak => "Long break",
+        }
+    }
+}
+
+/// A Pomodoro session cycling between work and breaks for one task.
+#[derive(Debug, Clone)]
+pub struct Pomodoro {
+    pub task: TaskId,
+    pub phase: PomodoroPhase,
+    pub phase_started: DateTime<Local>,
+    pub phase_length: TimeDelta,
+    /// Time left in the current phase, refreshed on every tick.
+    pub remaining: TimeDelta,
+    /// Work phases finished in this session, used to space out long breaks.
+    pub completed_work: u32,
+    /// Ticks left of the screen flash that marks a phase change.
+    pub flash_ticks: u32,
+}
+
+impl Pomodoro {
+    /// Share of the current phase that has passed, from 0 to 1.
+    pub fn progress(&self) -> f64 {
+        let length = self.phase_length.num_milliseconds().max(1) as f64;
+        let passed = (self.phase_length - self.remaining).num_milliseconds() as f64;
+        (passed / length).clamp(0.0, 1.0)
+    }
+}
+
+impl App {
+    /// Starts a Pomodoro session on the task, or cancels the running one if it is for the same
+    /// task. A session for another task is replaced by a new one on this task.
+    pub fn toggle_pomodoro(&mut self, id: TaskId) {
+        if self
+            .pomodoro
+            .take_if(|pomodoro| pomodoro.task == id)
+            .is_some()
+        {
+            self.status_message = Some("Pomodoro cancelled".to_string());
+            return;
+        }
+        if !self.index.contains_key(&id) {
+            return;
+        }
+        if self.pomodoro.is_some() {
+            self.status_message = Some("Pomodoro switched to the selected task".to_string());
+        }
+        let phase_length = self.phase_length(PomodoroPhase::Work);
+        self.pomodoro = Some(Pomodoro {
+            task: id,
+            phase: PomodoroPhase::Work,
+            phase_started: Local::now(),
+            phase_length,
+            remaining: phase_length,
+            completed_work: 0,
+            flash_ticks: 0,
+        });
+    }
+
+    /// Counts down the running session and moves on to the next phase when time is up.
+    /// Returns whether the phase changed.
+    pub fn update_pomodoro(&mut self) -> bool {
+        let Some(pomodoro) = &mut self.pomodoro else {
+            return false;
+        };
+        pomodoro.flash_ticks = pomodoro.flash_ticks.saturating_sub(1);
+        pomodoro.remaining = pomodoro.phase_length - (Local::now() - pomodoro.phase_started);
+        if pomodoro.remaining > TimeDelta::zero() {
+            return false;
+        }
+
+        let (task, finished) = (pomodoro.task, pomodoro.phase);
+        let next_phase = match finished {
+            PomodoroPhase::Work => {
+                pomodoro.completed_work += 1;
+                let every = self.config.pomodoro.long_break_every.max(1);
+                if pomodoro.completed_work % every == 0 {
+                    PomodoroPhase::LongBreak
+                } else {
+                    PomodoroPhase::ShortBreak
+                }
+            }
+            PomodoroPhase::ShortBreak | PomodoroPhase::LongBreak => PomodoroPhase::Work,
+        };
+        let phase_length = self.phase_length(next_phase);
+        if let Some(pomodoro) = &mut self.pomodoro {
+            pomodoro.phase = next_phase;
+            pomodoro.phase_started = Local::now();
+            pomodoro.phase_length = phase_length;
+            pomodoro.remaining = phase_length;
+            if self.config.pomodoro.flash {
+                pomodoro.flash_ticks = TICK_FPS as u32;
+            }
+        }
+        if finished == PomodoroPhase::Work {
+            if let Some(task) = self.task_mut(task) {
+                task.pomodoros += 1;
+            }
+        }
+        if self.config.pomodoro.bell {
+            // Ignore the result as a missing bell is not worth interrupting the session for
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(b"\x07").and_then(|()| stdout.flush());
+        }
+        self.status_message = Some(format!(
+            "{} finished, starting {}",
+            finished.as_str(),
+            next_phase.as_str().to_lowercase()
+        ));
+        true
+    }
+
+    fn phase_length(&self, phase: PomodoroPhase) -> TimeDelta {
+        let config = &self.config.pomodoro;
+        let minutes = match phase {
+            PomodoroPhase::Work => config.work_minutes,
+            PomodoroPhase::ShortBreak => config.short_break_minutes,
+            PomodoroPhase::LongBreak => config.long_break_minutes,
+        };
+        TimeDelta::minutes(i64::from(minutes.max(1)))
+    }
+}
@@ -0,0 +1,219 @@
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// How a task repeats once it is completed, typed inline as `rec:<rule>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Recurrence {
+    /// `rec:daily`
+    Daily,
+    /// `rec:weekly:mon,thu`, or `rec:weekly` to repeat on the same weekday.
+    Weekly { weekdays: Vec<Weekday> },
+    /// `rec:monthly:15`, or `rec:monthly` to repeat on the same day of the month.
+    Monthly { day: Option<u32> },
+    /// `rec:+3d`: due a number of days after the task was last completed.
+    AfterCompletion { days: u64 },
+}
+
+impl Recurrence {
+    /// Parses the rule after `rec:`, e.g. `weekly:mon,thu`.
+    pub fn parse(rule: &str) -> Option<Self> {
+        let rule = rule.to_lowercase();
+        let (kind, argument) = match rule.split_once(':') {
+            Some((kind, argument)) => (kind, Some(argument)),
+            None => (rule.as_str(), None),
+        };
+        match (kind, argument) {
+            ("daily", None) => Some(Recurrence::Daily),
+            ("weekly", None) => Some(Recurrence::Weekly {
+                weekdays: Vec::new(),
+            }),
+            ("weekly", Some(days)) => {
+                let mut weekdays = days
+                    .split(',')
+                    .map(|day| day.parse::<Weekday>().ok())
+                    .collect::<Option<Vec<_>>>()?;
+                weekdays.sort_by_key(|day| day.num_days_from_monday());
+                weekdays.dedup();
+                Some(Recurrence::Weekly { weekdays })
+            }
+            ("monthly", None) => Some(Recurrence::Monthly { day: None }),
+            ("monthly", Some(day)) => {
+                let day = day.parse().ok().filter(|day| (1..=31).contains(day))?;
+                Some(Recurrence::Monthly { day: Some(day) })
+            }
+            (relative, None) if relative.starts_with('+') && relative.ends_with('d') => {
+                let days = relative[1..relative.len() - 1].parse().ok()?;
+                (days > 0).then_some(Recurrence::AfterCompletion { days })
+            }
+            _ => None,
+        }
+    }
+
+    /// Renders the rule back into the form [`Recurrence::parse`] understands.
+    pub fn as_input(&self) -> String {
+        match self {
+            Recurrence::Daily => "daily".to_string(),
+            Recurrence::Weekly { weekdays } if weekdays.is_empty() => "weekly".to_string(),
+            Recurrence::Weekly { weekdays } => {
+                let days: Vec<String> = weekdays
+                    .iter()
+                    .map(|day| day.to_string().to_lowercase())
+                    .collect();
+                format!("weekly:{}", days.join(","))
+            }
+            Recurrence::Monthly { day: None } => "monthly".to_string(),
+            Recurrence::Monthly { day: Some(day) } => format!("monthly:{}", day),
+            Recurrence::AfterCompletion { days } => format!("+{}d", days),
+        }
+    }
+
+    /// A short human-readable description, e.g. `every Mon, Thu`.
+    pub fn describe(&self) -> String {
+        match self {
+            Recurrence::Daily => "every day".to_string(),
+            Recurrence::Weekly { weekdays } if weekdays.is_empty() => "every week".to_string(),
+            Recurrence::Weekly { weekdays } => {
+                let days: Vec<String> = weekdays.iter().map(Weekday::to_string).collect();
+                format!("every {}", days.join(", "))
+            }
+            Recurrence::Monthly { day: None } => "every month".to_string(),
+            Recurrence::Monthly { day: Some(day) } => format!("monthly on day {}", day),
+            Recurrence::AfterCompletion { days } => {
+                format!("{} day(s) after completion", days)
+            }
+        }
+    }
+
+    /// The due date of the occurrence after one due on `due` and completed on `completed_on`.
+    /// Occurrences that were missed while the task was overdue are skipped.
+    pub fn next_due(&self, due: Option<NaiveDate>, completed_on: NaiveDate) -> Option<NaiveDate> {
+        let after = due.map_or(completed_on, |due| due.max(completed_on));
+        match self {
+            Recurrence::Daily => after.checked_add_days(Days::new(1)),
+            Recurrence::Weekly { weekdays } if weekdays.is_empty() => {
+                let weekday = due.unwrap_or(completed_on).weekday();
+                next_matching(after, |date| date.weekday() == weekday)
+            }
+            Recurrence::Weekly { weekdays } => {
+                next_matching(after, |date| weekdays.contains(&date.weekday()))
+            }
+            Recurrence::Monthly { day } => {
+                let day = day.unwrap_or_else(|| due.unwrap_or(completed_on).day());
+                (0..=1)
+                    .filter_map(|months| {
+                        let month = after.checked_add_months(Months::new(months))?;
+                        day_of_month(month.year(), month.month(), day)
+                    })
+                    .find(|&date| date > after)
+            }
+            Recurrence::AfterCompletion { days } => completed_on.checked_add_days(Days::new(*days)),
+        }
+    }
+}
+
+/// The first day after `after` that satisfies `matches`, looking up to a week ahead.
+fn next_matching(after: NaiveDate, matches: impl Fn(NaiveDate) -> bool) -> Option<NaiveDate> {
+    (1..=7)
+        .filter_map(|days| after.checked_add_days(Days::new(days)))
+        .find(|&date| matches(date))
+}
+
+/// Day `day` of the month, or the month's last day if it is shorter.
+fn day_of_month(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    (day.min(28)..=day)
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn monthly_clamps_to_the_end_of_shorter_months() {
+        let rule = Recurrence::Monthly { day: None };
+        let due = date(2023, 1, 31);
+        assert_eq!(rule.next_due(Some(due), due), Some(date(2023, 2, 28)));
+        let due = date(2024, 1, 31);
+        assert_eq!(rule.next_due(Some(due), due), Some(date(2024, 2, 29)));
+        // The day asked for comes back once the month is long enough
+        let rule = Recurrence::Monthly { day: Some(31) };
+        let due = date(2024, 2, 29);
+        assert_eq!(rule.next_due(Some(due), due), Some(date(2024, 3, 31)));
+    }
+
+    #[test]
+    fn monthly_rolls_over_into_the_next_year() {
+        let rule = Recurrence::Monthly { day: Some(15) };
+        let due = date(2023, 12, 15);
+        assert_eq!(rule.next_due(Some(due), due), Some(date(2024, 1, 15)));
+    }
+
+    #[test]
+    fn weekly_steps_to_the_next_matching_weekday() {
+        // 2024-05-10 is a Friday
+        let friday = date(2024, 5, 10);
+        let same_day = Recurrence::Weekly {
+            weekdays: Vec::new(),
+        };
+        assert_eq!(
+            same_day.next_due(Some(friday), friday),
+            Some(date(2024, 5, 17))
+        );
+        let twice = Recurrence::Weekly {
+            weekdays: vec![Weekday::Mon, Weekday::Thu],
+        };
+        assert_eq!(
+            twice.next_due(Some(friday), friday),
+            Some(date(2024, 5, 13))
+        );
+        let monday = date(2024, 5, 13);
+        assert_eq!(
+            twice.next_due(Some(monday), monday),
+            Some(date(2024, 5, 16))
+        );
+    }
+
+    #[test]
+    fn daily_skips_occurrences_missed_while_overdue() {
+        let due = date(2024, 5, 1);
+        let completed_on = date(2024, 5, 10);
+        assert_eq!(
+            Recurrence::Daily.next_due(Some(due), completed_on),
+            Some(date(2024, 5, 11))
+        );
+        // Completing early keeps to the schedule
+        assert_eq!(
+            Recurrence::Daily.next_due(Some(completed_on), due),
+            Some(date(2024, 5, 11))
+        );
+    }
+
+    #[test]
+    fn after_completion_counts_from_the_completion_not_the_due_date() {
+        let rule = Recurrence::AfterCompletion { days: 3 };
+        let due = date(2024, 5, 1);
+        assert_eq!(
+            rule.next_due(Some(due), date(2024, 5, 10)),
+            Some(date(2024, 5, 13))
+        );
+        assert_eq!(
+            rule.next_due(Some(date(2024, 5, 20)), date(2024, 5, 10)),
+            Some(date(2024, 5, 13))
+        );
+        assert_eq!(rule.next_due(None, due), Some(date(2024, 5, 4)));
+    }
+
+    #[test]
+    fn tasks_without_a_due_date_recur_from_their_completion() {
+        let completed_on = date(2024, 5, 10);
+        assert_eq!(
+            Recurrence::Monthly { day: None }.next_due(None, completed_on),
+            Some(date(2024, 6, 10))
+        );
+    }
+}
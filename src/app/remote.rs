@@ -0,0 +1,90 @@
+use super::App;
+use crate::control::{self, Notification};
+use crate::server::{self, SharedTasks};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+/// Notifications kept for a subscriber that is slow to read before it starts missing some.
+const NOTIFICATION_BACKLOG: usize = 64;
+
+impl App {
+    /// Starts the HTTP API and the control socket if they are enabled.
+    pub fn start_remote(&mut self) {
+        if self.config.api.enabled {
+            let tasks = self.shared_tasks();
+            let token = self.config.api.token.clone();
+            match server::start(self.config.api.port, token, self.events.sender(), tasks) {
+                Ok(address) => {
+                    self.status_message = Some(format!("API listening on http://{}", address))
+                }
+                Err(err) => self.status_message = Some(format!("API not started: {}", err)),
+            }
+        }
+        if self.config.control.enabled {
+            let tasks = self.shared_tasks();
+            let (notifications, _) = broadcast::channel(NOTIFICATION_BACKLOG);
+            let path = Path::new(&self.config.control.socket);
+            match control::start(path, self.events.sender(), tasks, notifications.clone()) {
+                Ok(()) => self.notifications = Some(notifications),
+                Err(err) => {
+                    self.status_message = Some(format!("Control socket not started: {}", err))
+                }
+            }
+        }
+    }
+
+    /// Removes the control socket on the way out.
+    pub fn stop_remote(&mut self) {
+        if self.notifications.take().is_some() {
+            let _ = fs::remove_file(&self.config.control.socket);
+        }
+    }
+
+    /// The tree shared with the API and control socket, created on first use.
+    fn shared_tasks(&mut self) -> SharedTasks {
+        self.shared_tasks
+            .get_or_insert_with(|| Arc::new(RwLock::new(self.tasks.clone())))
+            .clone()
+    }
+
+    /// Hands the current tree to the API and control socket and tells subscribers it changed.
+    pub fn publish_tasks(&self) {
+        if let Some(shared_tasks) = &self.shared_tasks {
+            if let Ok(mut shared_tasks) = shared_tasks.write() {
+                *shared_tasks = self.tasks.clone();
+            }
+        }
+        if let Some(notifications) = &self.notifications {
+            let total = self.index.len();
+            let open = self
+                .index
+                .keys()
+                .filter_map(|&id| self.task(id))
+                .filter(|task| !task.completed)
+                .count();
+            // Sending only fails when nobody is subscribed
+            let _ = notifications.send(Notification::TasksChanged { open, total });
+        }
+    }
+
+    /// Tells subscribers when the selection moved to another task.
+    pub fn notify_selection(&mut self) {
+        let Some(notifications) = &self.notifications else {
+            return;
+        };
+        if self.notified_selection == self.selected_task {
+            return;
+        }
+        self.notified_selection = self.selected_task;
+        let name = self
+            .selected_task
+            .and_then(|id| self.task(id))
+            .map(|task| task.name.clone());
+        let _ = notifications.send(Notification::SelectionChanged {
+            id: self.selected_task,
+            name,
+        });
+    }
+}
@@ -1,6 +1,33 @@
+use crate::app::task::TaskId;
+use crate::event::AppEvent;
+
 #[derive(Debug, PartialEq)]
 pub enum AppMode {
     Normal,
     Editing,
-    EditingTask { path: Vec<usize> },
+    EditingTask {
+        id: TaskId,
+    },
+    /// Marks every row between `anchor` and the selection.
+    Visual {
+        anchor: usize,
+    },
+    Tagging,
+    /// Typing how many levels of the tree to show.
+    ExpandDepth,
+    /// Browsing the archive instead of the task tree.
+    Archive,
+    /// Browsing the trash instead of the task tree.
+    Trash,
+    /// Browsing backups of the data file, with a preview of the selected one.
+    Backups,
+    /// Browsing the git history of the data file, with the changes made in the selected commit.
+    History,
+    /// Going through the conflicts a merge left, choosing a side for each.
+    Conflicts,
+    /// A modal yes/no prompt; `on_confirm` is sent once the user accepts.
+    Confirm {
+        message: String,
+        on_confirm: AppEvent,
+    },
 }
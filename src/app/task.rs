@@ -1,16 +1,301 @@
-#[derive(Debug, Clone)]
+use crate::app::parse;
+use crate::app::recurrence::Recurrence;
+use crate::app::timer::TimeEntry;
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Identifies a task for its whole lifetime, wherever it is moved in the tree.
+pub type TaskId = Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Task {
+    #[serde(default = "Uuid::new_v4")]
+    pub id: TaskId,
     pub name: String,
+    #[serde(default)]
     pub subtasks: Vec<Task>,
+    #[serde(default)]
     pub expanded: bool,
+    #[serde(default)]
+    pub completed: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub due: Option<NaiveDate>,
+    #[serde(default = "Local::now")]
+    pub created: DateTime<Local>,
+    /// Last time the task itself was edited, completed or reopened.
+    #[serde(default = "Local::now")]
+    pub modified: DateTime<Local>,
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// Tasks anywhere in the tree that must be completed before this one.
+    #[serde(default)]
+    pub blocked_by: Vec<TaskId>,
+    #[serde(default)]
+    pub time_log: Vec<TimeEntry>,
+    /// Pomodoro work phases finished on this task.
+    #[serde(default)]
+    pub pomodoros: u32,
+    #[serde(default)]
+    pub notes: Vec<Note>,
+}
+
+/// A dated remark kept with a task, such as an annotation imported from Taskwarrior.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub added: DateTime<Local>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl Priority {
+    /// Parses the word used after `!` in task input, e.g. `high` or `h`.
+    pub fn parse(word: &str) -> Option<Self> {
+        match word.to_lowercase().as_str() {
+            "low" | "l" => Some(Priority::Low),
+            "medium" | "med" | "m" => Some(Priority::Medium),
+            "high" | "h" => Some(Priority::High),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        }
+    }
 }
 
 impl Task {
     pub fn new(name: String) -> Self {
+        let now = Local::now();
         Self {
+            id: Uuid::new_v4(),
             name,
             subtasks: Vec::new(),
             expanded: false,
+            completed: false,
+            tags: Vec::new(),
+            priority: None,
+            due: None,
+            created: now,
+            modified: now,
+            completed_at: None,
+            recurrence: None,
+            blocked_by: Vec::new(),
+            time_log: Vec::new(),
+            pomodoros: 0,
+            notes: Vec::new(),
+        }
+    }
+
+    /// Builds a task from typed input, picking up inline attributes such as `#tag`.
+    pub fn from_input(input: &str) -> Self {
+        let mut task = Self::new(String::new());
+        task.set_from_input(input);
+        task
+    }
+
+    /// Replaces the name and inline attributes with those parsed from `input`.
+    pub fn set_from_input(&mut self, input: &str) {
+        let parsed = parse::parse_task_input(input, Local::now().date_naive());
+        self.name = parsed.name;
+        self.tags = parsed.tags;
+        self.priority = parsed.priority;
+        self.due = parsed.due;
+        self.recurrence = parsed.recurrence;
+        self.modified = Local::now();
+    }
+
+    /// Completes or reopens the task, recording when it happened.
+    pub fn set_completed(&mut self, completed: bool) {
+        if self.completed == completed {
+            return;
+        }
+        let now = Local::now();
+        self.completed = completed;
+        self.completed_at = completed.then_some(now);
+        self.modified = now;
+    }
+
+    /// The next occurrence of a recurring task that was just completed: a fresh, open copy of
+    /// the subtree due on the rule's next date. The completed task keeps its place as history
+    /// and hands the rule on to the copy.
+    pub fn next_occurrence(&mut self) -> Option<Task> {
+        let recurrence = self.recurrence.take()?;
+        let completed_on = self
+            .completed_at
+            .map_or_else(|| Local::now().date_naive(), |at| at.date_naive());
+        let mut next = self.with_fresh_ids();
+        next.reopen_recursive();
+        next.clear_time_log_recursive();
+        next.created = Local::now();
+        next.due = recurrence.next_due(self.due, completed_on);
+        next.recurrence = Some(recurrence);
+        Some(next)
+    }
+
+    /// Marks this task and every task below it as not completed.
+    fn reopen_recursive(&mut self) {
+        self.set_completed(false);
+        for subtask in &mut self.subtasks {
+            subtask.reopen_recursive();
+        }
+    }
+
+    /// Forgets the time logged on this task and every task below it.
+    fn clear_time_log_recursive(&mut self) {
+        self.time_log.clear();
+        self.pomodoros = 0;
+        for subtask in &mut self.subtasks {
+            subtask.clear_time_log_recursive();
         }
     }
+
+    /// Copies the subtree, giving the copy and all of its subtasks new IDs. Dependencies
+    /// between tasks inside the subtree follow to the copies; those on tasks outside it stay.
+    pub fn with_fresh_ids(&self) -> Self {
+        let mut fresh_ids = HashMap::new();
+        let mut copy = self.clone();
+        copy.assign_fresh_ids(&mut fresh_ids);
+        copy.remap_blocked_by(&fresh_ids);
+        copy
+    }
+
+    /// Gives this task and every task below it a new ID, recording each old ID's replacement.
+    fn assign_fresh_ids(&mut self, fresh_ids: &mut HashMap<TaskId, TaskId>) {
+        let fresh_id = Uuid::new_v4();
+        fresh_ids.insert(self.id, fresh_id);
+        self.id = fresh_id;
+        for subtask in &mut self.subtasks {
+            subtask.assign_fresh_ids(fresh_ids);
+        }
+    }
+
+    /// Points the dependencies of this task and every task below it at the replacement IDs.
+    fn remap_blocked_by(&mut self, fresh_ids: &HashMap<TaskId, TaskId>) {
+        for blocker in &mut self.blocked_by {
+            if let Some(&fresh_id) = fresh_ids.get(blocker) {
+                *blocker = fresh_id;
+            }
+        }
+        for subtask in &mut self.subtasks {
+            subtask.remap_blocked_by(fresh_ids);
+        }
+    }
+
+    /// Whether this task or any task below it satisfies `matches`.
+    pub fn any_in_subtree(&self, matches: &impl Fn(&Task) -> bool) -> bool {
+        matches(self)
+            || self
+                .subtasks
+                .iter()
+                .any(|subtask| subtask.any_in_subtree(matches))
+    }
+
+    /// Counts every task below this one, at any depth.
+    pub fn descendant_count(&self) -> usize {
+        self.subtasks
+            .iter()
+            .map(|subtask| 1 + subtask.descendant_count())
+            .sum()
+    }
+
+    /// Completed and total tasks below this one, at any depth.
+    pub fn completion_counts(&self) -> (usize, usize) {
+        self.subtasks
+            .iter()
+            .fold((0, 0), |(completed, total), subtask| {
+                let (sub_completed, sub_total) = subtask.completion_counts();
+                (
+                    completed + sub_completed + usize::from(subtask.completed),
+                    total + sub_total + 1,
+                )
+            })
+    }
+
+    /// Share of the task that is done, from 0 to 1. A completed task is done; otherwise each
+    /// subtask contributes its own progress in equal parts.
+    pub fn weighted_progress(&self) -> f64 {
+        if self.completed {
+            return 1.0;
+        }
+        if self.subtasks.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self.subtasks.iter().map(Task::weighted_progress).sum();
+        sum / self.subtasks.len() as f64
+    }
+
+    /// Expands or collapses this task and every task below it.
+    pub fn set_expanded_recursive(&mut self, expanded: bool) {
+        self.expanded = expanded && !self.subtasks.is_empty();
+        for subtask in &mut self.subtasks {
+            subtask.set_expanded_recursive(expanded);
+        }
+    }
+
+    /// Adds `tag` unless the task already carries it.
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.tags.iter().any(|existing| existing == tag) {
+            self.tags.push(tag.to_string());
+            self.modified = Local::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fresh_copies_keep_dependencies_inside_the_subtree() {
+        let outside = Uuid::new_v4();
+        let mut trip = Task::new("Plan trip".to_string());
+        let flights = Task::new("Book flights".to_string());
+        let mut hotel = Task::new("Book hotel".to_string());
+        hotel.blocked_by = vec![flights.id, outside];
+        trip.subtasks = vec![flights, hotel];
+
+        let copy = trip.with_fresh_ids();
+
+        let (flights, hotel) = (&copy.subtasks[0], &copy.subtasks[1]);
+        assert_ne!(flights.id, trip.subtasks[0].id);
+        assert_eq!(hotel.blocked_by, [flights.id, outside]);
+        assert_eq!(trip.subtasks[1].blocked_by, [trip.subtasks[0].id, outside]);
+    }
+
+    #[test]
+    fn next_occurrence_blocks_on_the_new_subtasks() {
+        let mut review = Task::new("Weekly review".to_string());
+        review.recurrence = Some(Recurrence::Weekly {
+            weekdays: Vec::new(),
+        });
+        let inbox = Task::new("Empty inbox".to_string());
+        let mut plan = Task::new("Plan next week".to_string());
+        plan.blocked_by = vec![inbox.id];
+        review.subtasks = vec![inbox, plan];
+        review.set_completed(true);
+
+        let next = review.next_occurrence().unwrap();
+
+        assert_eq!(next.subtasks[1].blocked_by, [next.subtasks[0].id]);
+        assert!(!next.subtasks[1].completed);
+        assert_eq!(review.subtasks[1].blocked_by, [review.subtasks[0].id]);
+    }
 }
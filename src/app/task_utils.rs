@@ -1,4 +1,15 @@
-use crate::app::task::Task;
+use crate::app::task::{Task, TaskId};
+use std::collections::{BTreeSet, HashMap};
+
+/// Finds a reference to a task in the tree using its path.
+pub fn get_task<'a>(tasks: &'a [Task], path: &[usize]) -> Option<&'a Task> {
+    let (first_index, rest_of_path) = path.split_first()?;
+    let mut current_task = tasks.get(*first_index)?;
+    for &index in rest_of_path {
+        current_task = current_task.subtasks.get(index)?;
+    }
+    Some(current_task)
+}
 
 /// Finds a mutable reference to a task in the tree using its path.
 pub fn get_task_mut<'a>(tasks: &'a mut [Task], path: &[usize]) -> Option<&'a mut Task> {
@@ -18,3 +29,157 @@ pub fn get_task_mut<'a>(tasks: &'a mut [Task], path: &[usize]) -> Option<&'a mut
 
     Some(current_task)
 }
+
+/// Finds the list of subtasks under `parent_path`, or the top level for an empty path.
+pub fn get_children_mut<'a>(
+    tasks: &'a mut Vec<Task>,
+    parent_path: &[usize],
+) -> Option<&'a mut Vec<Task>> {
+    if parent_path.is_empty() {
+        Some(tasks)
+    } else {
+        Some(&mut get_task_mut(tasks, parent_path)?.subtasks)
+    }
+}
+
+/// Removes the task at `path` from the tree, returning it with its subtree.
+pub fn remove_task(tasks: &mut Vec<Task>, path: &[usize]) -> Option<Task> {
+    let (&task_index, parent_path) = path.split_last()?;
+    let siblings = get_children_mut(tasks, parent_path)?;
+    if task_index < siblings.len() {
+        Some(siblings.remove(task_index))
+    } else {
+        None
+    }
+}
+
+/// Drops every path that lies inside another path of the set, so each subtree is handled once.
+pub fn outermost_paths(paths: &BTreeSet<Vec<usize>>) -> Vec<Vec<usize>> {
+    // The set is ordered lexicographically, so an ancestor always comes before its descendants.
+    let mut outermost: Vec<Vec<usize>> = Vec::new();
+    for path in paths {
+        if !outermost.iter().any(|kept| path.starts_with(kept)) {
+            outermost.push(path.clone());
+        }
+    }
+    outermost
+}
+
+/// Removes the tasks at `paths` and returns them in tree order.
+///
+/// Paths must come from [`outermost_paths`]. They are removed from last to first, so taking one
+/// task out never shifts the index of a path that is still waiting to be removed.
+pub fn remove_tasks(tasks: &mut Vec<Task>, paths: &[Vec<usize>]) -> Vec<Task> {
+    let mut removed: Vec<Task> = paths
+        .iter()
+        .rev()
+        .filter_map(|path| remove_task(tasks, path))
+        .collect();
+    removed.reverse();
+    removed
+}
+
+/// Expands the tree so that exactly `depth` levels are visible. A depth of 1 collapses
+/// everything.
+pub fn expand_to_depth(tasks: &mut [Task], depth: usize) {
+    for task in tasks {
+        task.expanded = depth > 1 && !task.subtasks.is_empty();
+        expand_to_depth(&mut task.subtasks, depth.saturating_sub(1));
+    }
+}
+
+/// Maps the ID of every task in the tree to its path.
+pub fn build_index(tasks: &[Task]) -> HashMap<TaskId, Vec<usize>> {
+    let mut index = HashMap::new();
+    index_tasks(tasks, &mut Vec::new(), &mut index);
+    index
+}
+
+fn index_tasks(tasks: &[Task], path: &mut Vec<usize>, index: &mut HashMap<TaskId, Vec<usize>>) {
+    for (i, task) in tasks.iter().enumerate() {
+        path.push(i);
+        index.insert(task.id, path.clone());
+        index_tasks(&task.subtasks, path, index);
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(name: &str, subtasks: Vec<Task>) -> Task {
+        let mut task = Task::new(name.to_string());
+        task.subtasks = subtasks;
+        task
+    }
+
+    /// Plan trip [Book flights, Pack [Clothes, Charger]], Water plants, Taxes [Receipts]
+    fn tree() -> Vec<Task> {
+        vec![
+            task(
+                "Plan trip",
+                vec![
+                    task("Book flights", Vec::new()),
+                    task(
+                        "Pack",
+                        vec![task("Clothes", Vec::new()), task("Charger", Vec::new())],
+                    ),
+                ],
+            ),
+            task("Water plants", Vec::new()),
+            task("Taxes", vec![task("Receipts", Vec::new())]),
+        ]
+    }
+
+    fn paths(paths: &[&[usize]]) -> BTreeSet<Vec<usize>> {
+        paths.iter().map(|path| path.to_vec()).collect()
+    }
+
+    fn names(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|task| task.name.as_str()).collect()
+    }
+
+    #[test]
+    fn removes_several_siblings() {
+        let mut tasks = tree();
+        let outermost = outermost_paths(&paths(&[&[2], &[0]]));
+        assert_eq!(outermost, [vec![0], vec![2]]);
+
+        let removed = remove_tasks(&mut tasks, &outermost);
+
+        assert_eq!(names(&removed), ["Plan trip", "Taxes"]);
+        assert_eq!(names(&tasks), ["Water plants"]);
+    }
+
+    #[test]
+    fn a_marked_parent_takes_its_marked_children_along() {
+        let mut tasks = tree();
+        let trip = tasks[0].clone();
+        let outermost = outermost_paths(&paths(&[&[0, 1, 1], &[0], &[0, 0]]));
+        assert_eq!(outermost, [vec![0]]);
+
+        let removed = remove_tasks(&mut tasks, &outermost);
+
+        assert_eq!(removed, [trip]);
+        assert_eq!(names(&tasks), ["Water plants", "Taxes"]);
+    }
+
+    #[test]
+    fn removes_tasks_at_different_depths_without_shifting_the_rest() {
+        let mut tasks = tree();
+        let outermost = outermost_paths(&paths(&[&[2, 0], &[0, 1, 1], &[1], &[0, 0]]));
+        assert_eq!(outermost.len(), 4);
+
+        let removed = remove_tasks(&mut tasks, &outermost);
+
+        assert_eq!(
+            names(&removed),
+            ["Book flights", "Charger", "Water plants", "Receipts"]
+        );
+        assert_eq!(names(&tasks), ["Plan trip", "Taxes"]);
+        assert_eq!(names(&tasks[0].subtasks), ["Pack"]);
+        assert_eq!(names(&tasks[0].subtasks[0].subtasks), ["Clothes"]);
+        assert!(tasks[1].subtasks.is_empty());
+    }
+}
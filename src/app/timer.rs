@@ -0,0 +1,107 @@
+use super::App;
+use crate::app::task::{Task, TaskId};
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+/// A stretch of time spent on a task.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeEntry {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+}
+
+impl TimeEntry {
+    pub fn duration(&self) -> TimeDelta {
+        self.end - self.start
+    }
+}
+
+/// The timer currently running. Only one task is timed at a time.
+#[derive(Debug, Clone)]
+pub struct ActiveTimer {
+    pub task: TaskId,
+    pub started: DateTime<Local>,
+    /// Time since `started`, refreshed on every tick for the live display.
+    pub elapsed: TimeDelta,
+}
+
+impl App {
+    /// Starts timing the task, stopping any other running timer first. Stops the timer
+    /// instead if it is already running on this task.
+    pub fn toggle_timer(&mut self, id: TaskId) {
+        let was_running = self.timer.as_ref().is_some_and(|timer| timer.task == id);
+        self.stop_timer();
+        if was_running || !self.index.contains_key(&id) {
+            return;
+        }
+        self.timer = Some(ActiveTimer {
+            task: id,
+            started: Local::now(),
+            elapsed: TimeDelta::zero(),
+        });
+    }
+
+    /// Stops the running timer and logs the time on its task.
+    pub fn stop_timer(&mut self) {
+        let Some(timer) = self.timer.take() else {
+            return;
+        };
+        let entry = TimeEntry {
+            start: timer.started,
+            end: Local::now(),
+        };
+        let logged = format_duration(entry.duration());
+        if let Some(task) = self.task_mut(timer.task) {
+            task.time_log.push(entry);
+            self.status_message = Some(format!("Logged {} on '{}'", logged, task.name));
+        }
+    }
+
+    /// Stops the timer if it runs on a task in one of the subtrees at `paths`, so its time is
+    /// logged before they leave the tree.
+    pub fn stop_timer_within(&mut self, paths: &[Vec<usize>]) {
+        let Some(timer_path) = self
+            .timer
+            .as_ref()
+            .and_then(|timer| self.path_of(timer.task))
+        else {
+            return;
+        };
+        if paths.iter().any(|path| timer_path.starts_with(path)) {
+            self.stop_timer();
+        }
+    }
+
+    /// Refreshes the elapsed time of the running timer.
+    pub fn update_timer(&mut self) {
+        if let Some(timer) = &mut self.timer {
+            timer.elapsed = Local::now() - timer.started;
+        }
+    }
+
+    /// Time logged on `task` and everything below it, including the running timer.
+    pub fn total_logged(&self, task: &Task) -> TimeDelta {
+        let mut total = task
+            .time_log
+            .iter()
+            .map(TimeEntry::duration)
+            .fold(TimeDelta::zero(), |total, duration| total + duration);
+        if let Some(timer) = self.timer.as_ref().filter(|timer| timer.task == task.id) {
+            total += timer.elapsed;
+        }
+        task.subtasks
+            .iter()
+            .fold(total, |total, subtask| total + self.total_logged(subtask))
+    }
+}
+
+/// Formats a duration as `H:MM:SS`.
+pub fn format_duration(duration: TimeDelta) -> String {
+    let seconds = duration.num_seconds().max(0);
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
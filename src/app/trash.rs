@@ -0,0 +1,76 @@
+use super::{clamp_list_state, App};
+use crate::app::location::Location;
+use crate::app::task::{Task, TaskId};
+use crate::storage;
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+/// A deleted subtree, kept until it is restored, purged or expires.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedTask {
+    pub task: Task,
+    #[serde(flatten)]
+    pub location: Location,
+    pub deleted_at: DateTime<Local>,
+}
+
+impl App {
+    /// Moves the tasks and their subtrees into the trash.
+    pub fn delete_tasks(&mut self, ids: &[TaskId]) {
+        let deleted_at = Local::now();
+        let taken = self.take_tasks(ids);
+        let count = taken.len();
+        self.trash
+            .extend(taken.into_iter().map(|(task, location)| TrashedTask {
+                task,
+                location,
+                deleted_at,
+            }));
+        self.marked.clear();
+        // The journal only records the tree, so save the trash now or a crash would lose
+        // the subtrees
+        self.status_message = Some(match storage::save_trash(&self.config, &self.trash) {
+            Ok(()) => format!("Moved {} task(s) to the trash", count),
+            Err(err) => format!("Moved {} task(s) to the trash, not saved: {}", count, err),
+        });
+    }
+
+    /// Puts a deleted task back where it came from, or as close to it as still exists.
+    pub fn restore_trashed(&mut self, id: TaskId) {
+        let Some(trash_index) = self.trash.iter().position(|entry| entry.task.id == id) else {
+            return;
+        };
+        let entry = self.trash.remove(trash_index);
+        clamp_list_state(&mut self.trash_list_state, self.trash.len());
+        let restored = self.reinsert_task(entry.task, &entry.location);
+        // Otherwise a crash would leave the task in the trash as well as in the tree
+        self.status_message = Some(match storage::save_trash(&self.config, &self.trash) {
+            Ok(()) => restored,
+            Err(err) => format!("{}, trash not saved: {}", restored, err),
+        });
+    }
+
+    /// Permanently removes the entries from the trash.
+    pub fn purge_trashed(&mut self, ids: &[TaskId]) {
+        let before = self.trash.len();
+        self.trash.retain(|entry| !ids.contains(&entry.task.id));
+        clamp_list_state(&mut self.trash_list_state, self.trash.len());
+        let purged = before - self.trash.len();
+        self.status_message = Some(match storage::save_trash(&self.config, &self.trash) {
+            Ok(()) => format!("Permanently deleted {} task(s)", purged),
+            Err(err) => format!("Permanently deleted {} task(s), not saved: {}", purged, err),
+        });
+    }
+
+    /// Purges entries that have been in the trash longer than the retention period. A
+    /// retention of zero days keeps them forever.
+    pub fn purge_expired_trash(&mut self) {
+        let retention_days = self.config.trash.retention_days;
+        if retention_days == 0 {
+            return;
+        }
+        let cutoff = Local::now() - TimeDelta::days(i64::from(retention_days));
+        self.trash.retain(|entry| entry.deleted_at > cutoff);
+        clamp_list_state(&mut self.trash_list_state, self.trash.len());
+    }
+}
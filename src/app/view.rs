@@ -0,0 +1,313 @@
+use super::App;
+use crate::app::task::{Task, TaskId};
+use crate::app::task_utils;
+use crate::config::{GroupKey, ProgressStyle, SortKey};
+use chrono::{Datelike, Local, NaiveDate};
+use std::cmp::Ordering;
+
+const SORT_KEYS: [SortKey; 6] = [
+    SortKey::Name,
+    SortKey::Created,
+    SortKey::Modified,
+    SortKey::Due,
+    SortKey::Priority,
+    SortKey::Completion,
+];
+
+/// Orders two tasks by `key`. Tasks missing the attribute sort last.
+pub fn compare_tasks(a: &Task, b: &Task, key: SortKey) -> Ordering {
+    match key {
+        SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        SortKey::Created => a.created.cmp(&b.created),
+        // Most recently touched first
+        SortKey::Modified => b.modified.cmp(&a.modified),
+        SortKey::Due => match (a.due, b.due) {
+            (Some(a_due), Some(b_due)) => a_due.cmp(&b_due),
+            (a_due, b_due) => a_due.is_none().cmp(&b_due.is_none()),
+        },
+        // Highest priority first
+        SortKey::Priority => b.priority.cmp(&a.priority),
+        // Open tasks first
+        SortKey::Completion => a.completed.cmp(&b.completed),
+    }
+}
+
+/// The due-date group a task falls into, relative to `today`.
+pub fn due_bucket(due: Option<NaiveDate>, today: NaiveDate) -> &'static str {
+    let days_left_in_week = 6 - i64::from(today.weekday().num_days_from_monday());
+    match due {
+        None => "No due date",
+        Some(due) if due < today => "Overdue",
+        Some(due) if due == today => "Today",
+        Some(due) if (due - today).num_days() <= days_left_in_week => "This week",
+        Some(_) => "Later",
+    }
+}
+
+impl App {
+    /// The order `tasks` are shown in: their stored order, or sorted by the active sort key.
+    /// The stored order itself is left untouched.
+    pub fn sorted_indices(&self, tasks: &[Task]) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..tasks.len()).collect();
+        if let Some(key) = self.sort {
+            indices.sort_by(|&a, &b| compare_tasks(&tasks[a], &tasks[b], key));
+        }
+        indices
+    }
+
+    /// The tasks at the top of the view: the zoomed-in task's subtasks, or the whole tree
+    /// when not zoomed.
+    pub fn view_root(&self) -> &[Task] {
+        match self.zoom.and_then(|id| self.task(id)) {
+            Some(root) => &root.subtasks,
+            None => &self.tasks,
+        }
+    }
+
+    /// Mutable access to the tasks at the top of the view.
+    pub fn view_root_mut(&mut self) -> &mut Vec<Task> {
+        let root_path = self
+            .zoom
+            .and_then(|id| self.path_of(id))
+            .unwrap_or_default();
+        task_utils::get_children_mut(&mut self.tasks, &root_path).unwrap()
+    }
+
+    /// Makes the selected task the temporary root of the view.
+    pub fn zoom_in(&mut self) {
+        if let Some(id) = self.selected_id() {
+            self.zoom = Some(id);
+            // Marks outside the zoomed subtree would be invisible, so start afresh
+            self.marked.clear();
+            let has_rows = !self.get_tasks_to_display().is_empty();
+            self.select_index(has_rows.then_some(0));
+        }
+    }
+
+    /// Moves the root of the view up to the parent of the zoomed-in task.
+    pub fn zoom_out(&mut self) {
+        if let Some(previous_root) = self.zoom {
+            let path = self.path_of(previous_root).unwrap_or_default();
+            self.zoom = path
+                .split_last()
+                .and_then(|(_, parent_path)| task_utils::get_task(&self.tasks, parent_path))
+                .map(|parent| parent.id);
+            self.marked.clear();
+            self.select_id(previous_root);
+        }
+    }
+
+    /// Names of the tasks from the top of the tree down to the zoomed-in task.
+    pub fn breadcrumb(&self) -> Vec<&str> {
+        let path = self
+            .zoom
+            .and_then(|id| self.index.get(&id))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        (1..=path.len())
+            .filter_map(|len| task_utils::get_task(&self.tasks, &path[..len]))
+            .map(|task| task.name.as_str())
+            .collect()
+    }
+
+    /// The completion summary shown after a parent task, e.g. ` [3/5 60%]`. Empty for tasks
+    /// without subtasks or when progress is hidden.
+    pub fn progress_label(&self, task: &Task) -> String {
+        let config = &self.config.theme.progress;
+        if task.subtasks.is_empty() || config.style == ProgressStyle::Hidden {
+            return String::new();
+        }
+        let (completed, total) = task.completion_counts();
+        let ratio = if config.weighted {
+            task.weighted_progress()
+        } else {
+            completed as f64 / total as f64
+        };
+        let percent = (ratio * 100.0).round();
+        match config.style {
+            ProgressStyle::Hidden => String::new(),
+            ProgressStyle::Count => format!(" [{}/{}]", completed, total),
+            ProgressStyle::Percent => format!(" [{}%]", percent),
+            ProgressStyle::CountAndPercent => format!(" [{}/{} {}%]", completed, total, percent),
+            ProgressStyle::Gauge => {
+                let filled = (ratio * config.gauge_width as f64).round() as usize;
+                format!(
+                    " [{}{}] {}%",
+                    "█".repeat(filled),
+                    "░".repeat(config.gauge_width.saturating_sub(filled)),
+                    percent
+                )
+            }
+        }
+    }
+
+    /// Steps through the sort keys, ending back at the stored order.
+    pub fn cycle_sort(&mut self) {
+        self.sort = match self.sort {
+            None => Some(SORT_KEYS[0]),
+            Some(key) => SORT_KEYS
+                .iter()
+                .position(|&candidate| candidate == key)
+                .and_then(|index| SORT_KEYS.get(index + 1))
+                .copied(),
+        };
+        self.status_message = Some(match self.sort {
+            Some(key) => format!("Sorted by {:?}", key),
+            None => "Showing stored order".to_string(),
+        });
+        self.clamp_selection();
+    }
+
+    /// Steps through the grouping views, ending back at the tree.
+    pub fn cycle_group(&mut self) {
+        self.group = match self.group {
+            None => Some(GroupKey::Tag),
+            Some(GroupKey::Tag) => Some(GroupKey::Due),
+            Some(GroupKey::Due) => None,
+        };
+        self.status_message = Some(match self.group {
+            Some(key) => format!("Grouped by {:?}", key),
+            None => "Showing task tree".to_string(),
+        });
+        self.clamp_selection();
+    }
+
+    /// Permanently reorders a task and its siblings by the active sort key, or by name if no
+    /// sort key is active.
+    pub fn sort_siblings(&mut self, id: TaskId) {
+        let Some(path) = self.path_of(id) else {
+            return;
+        };
+        let key = self.sort.unwrap_or(SortKey::Name);
+        let Some(siblings) = task_utils::get_children_mut(&mut self.tasks, &path[..path.len() - 1])
+        else {
+            return;
+        };
+        siblings.sort_by(|a, b| compare_tasks(a, b, key));
+        self.status_message = Some(format!("Sorted siblings by {:?}", key));
+    }
+
+    /// Every task in the view as a flat list under group headings. Header rows have no ID.
+    pub(super) fn get_grouped_tasks_to_display(
+        &self,
+        key: GroupKey,
+    ) -> Vec<(String, Option<TaskId>)> {
+        let mut all_tasks = Vec::new();
+        collect_tasks(self.view_root(), &mut all_tasks);
+        if let Some(sort_key) = self.sort {
+            all_tasks.sort_by(|a, b| compare_tasks(a, b, sort_key));
+        }
+
+        let groups: Vec<String> = match key {
+            GroupKey::Tag => {
+                let mut tags: Vec<String> = all_tasks
+                    .iter()
+                    .flat_map(|task| task.tags.iter().cloned())
+                    .collect();
+                tags.sort();
+                tags.dedup();
+                tags.into_iter()
+                    .map(|tag| format!("#{}", tag))
+                    .chain(std::iter::once("Untagged".to_string()))
+                    .collect()
+            }
+            GroupKey::Due => ["Overdue", "Today", "This week", "Later", "No due date"]
+                .iter()
+                .map(|bucket| bucket.to_string())
+                .collect(),
+        };
+
+        let today = Local::now().date_naive();
+        let mut display_tasks = Vec::new();
+        for group in groups {
+            let members: Vec<_> = all_tasks
+                .iter()
+                .filter(|task| match key {
+                    GroupKey::Tag if group == "Untagged" => task.tags.is_empty(),
+                    GroupKey::Tag => task.tags.iter().any(|tag| group[1..] == *tag),
+                    GroupKey::Due => due_bucket(task.due, today) == group,
+                })
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            display_tasks.push((format!("── {} ──", group), None));
+            for task in members {
+                display_tasks.push((format!("  {}", task.name), Some(task.id)));
+            }
+        }
+        display_tasks
+    }
+}
+
+/// Collects every task in the tree, in tree order.
+fn collect_tasks<'a>(tasks: &'a [Task], all_tasks: &mut Vec<&'a Task>) {
+    for task in tasks {
+        all_tasks.push(task);
+        collect_tasks(&task.subtasks, all_tasks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Vec<Task> {
+        let mut pack = Task::new("Pack".to_string());
+        pack.subtasks.push(Task::new("Clothes".to_string()));
+        let mut trip = Task::new("Plan trip".to_string());
+        trip.subtasks = vec![Task::new("Book flights".to_string()), pack];
+        vec![trip, Task::new("Water plants".to_string())]
+    }
+
+    fn names(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|task| task.name.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn zooming_in_shows_only_the_subtree() {
+        let tasks = tree();
+        let (trip, flights, plants) = (tasks[0].id, tasks[0].subtasks[0].id, tasks[1].id);
+        let mut app = App::for_tests(tasks);
+        app.marked.insert(plants);
+        app.select_id(trip);
+
+        app.zoom_in();
+
+        assert_eq!(names(app.view_root()), ["Book flights", "Pack"]);
+        assert_eq!(app.breadcrumb(), ["Plan trip"]);
+        assert_eq!(app.selected_id(), Some(flights));
+        assert!(app.marked.is_empty());
+    }
+
+    #[tokio::test]
+    async fn zooming_out_steps_up_one_level_at_a_time() {
+        let tasks = tree();
+        let (trip, pack) = (tasks[0].id, tasks[0].subtasks[1].id);
+        let mut app = App::for_tests(tasks);
+        app.zoom = Some(pack);
+
+        app.zoom_out();
+        assert_eq!(app.zoom, Some(trip));
+        assert_eq!(app.selected_id(), Some(pack));
+
+        app.zoom_out();
+        assert_eq!(app.zoom, None);
+        assert_eq!(app.selected_id(), Some(trip));
+        assert_eq!(names(app.view_root()), ["Plan trip", "Water plants"]);
+    }
+
+    #[tokio::test]
+    async fn removing_the_zoomed_task_shows_the_whole_tree() {
+        let tasks = tree();
+        let pack = tasks[0].subtasks[1].id;
+        let mut app = App::for_tests(tasks);
+        app.zoom = Some(pack);
+
+        app.tasks[0].subtasks.pop();
+        app.reindex();
+
+        assert_eq!(app.zoom, None);
+        assert!(app.breadcrumb().is_empty());
+    }
+}
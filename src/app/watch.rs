@@ -0,0 +1,68 @@
+use super::App;
+use crate::app::flat;
+use crate::app::merge;
+use crate::storage;
+use std::time::{Duration, Instant};
+
+impl App {
+    /// Saves the tree once no change has been made for `sync_seconds`, so other instances
+    /// working on the same file see it.
+    pub fn save_if_settled(&mut self) {
+        let settle = Duration::from_secs(self.config.storage.sync_seconds);
+        if self
+            .unsaved_since
+            .is_some_and(|since| since.elapsed() >= settle)
+        {
+            if let Err(err) = self.snapshot() {
+                self.status_message = Some(format!("Saving failed: {}", err));
+                self.unsaved_since = None;
+            }
+        }
+    }
+
+    /// Every `sync_seconds`, looks for changes another instance saved to the data file and
+    /// merges them into the tree.
+    pub fn watch_data_file(&mut self) {
+        let interval = self.config.storage.sync_seconds;
+        if interval == 0 || self.last_disk_check.elapsed() < Duration::from_secs(interval) {
+            return;
+        }
+        self.last_disk_check = Instant::now();
+        match self.merge_external_changes() {
+            Ok(true) => {
+                self.record_changes();
+                self.status_message = Some(format!(
+                    "Merged changes saved to {} elsewhere",
+                    self.config.storage.path
+                ));
+            }
+            Ok(false) => {}
+            Err(err) => {
+                self.status_message = Some(format!(
+                    "Could not check {}: {}",
+                    self.config.storage.path, err
+                ))
+            }
+        }
+    }
+
+    /// If the data file changed since it was last read or written here, merges those changes
+    /// into the tree and returns `true`. The selection and what is expanded stay as they are;
+    /// tasks changed on both sides open the conflict screen.
+    pub fn merge_external_changes(&mut self) -> color_eyre::Result<bool> {
+        if !storage::data_file_changed(&self.config, &mut self.disk_version)? {
+            return Ok(false);
+        }
+        let (tasks, version) = storage::load_tasks_versioned(&self.config)?;
+        let theirs = flat::flatten(&tasks);
+        let ours = flat::flatten(&self.tasks);
+        let merged = merge::merge(&self.disk_tree, &ours, &theirs);
+        self.tasks = flat::unflatten(&merged.tree);
+        self.disk_tree = theirs;
+        self.disk_version = version;
+        self.reindex();
+        self.sync_selection();
+        self.add_conflicts(merged.conflicts);
+        Ok(true)
+    }
+}
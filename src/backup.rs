@@ -0,0 +1,140 @@
+use crate::app::task::Task;
+use crate::config::{BackupInterval, Config};
+use crate::storage;
+use chrono::{DateTime, Local, NaiveDateTime, SubsecRound, TimeDelta, TimeZone};
+use color_eyre::eyre::{eyre, WrapErr};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FILE_PREFIX: &str = "tasks-";
+/// Microseconds keep backups made within the same second apart.
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.6f";
+
+/// A saved copy of the task data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub created: DateTime<Local>,
+}
+
+/// Lists the backups in the backup directory, newest first.
+pub fn list_backups(config: &Config) -> color_eyre::Result<Vec<Backup>> {
+    let dir = Path::new(&config.backup.dir);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups: Vec<Backup> = fs::read_dir(dir)
+        .wrap_err_with(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let stem = path.file_stem()?.to_str()?.strip_prefix(FILE_PREFIX)?;
+            let created = NaiveDateTime::parse_from_str(stem, TIMESTAMP_FORMAT).ok()?;
+            let created = Local.from_local_datetime(&created).earliest()?;
+            Some(Backup { path, created })
+        })
+        .collect();
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created));
+    Ok(backups)
+}
+
+/// Whether the configured interval has passed since `last_backup`.
+pub fn is_due(config: &Config, last_backup: Option<DateTime<Local>>) -> bool {
+    let interval = match config.backup.interval {
+        BackupInterval::Off => return false,
+        BackupInterval::Hourly => TimeDelta::hours(1),
+        BackupInterval::Daily => TimeDelta::days(1),
+    };
+    last_backup.is_none_or(|last| Local::now() - last >= interval)
+}
+
+/// Writes a new backup of `tasks` and removes the oldest ones beyond the configured count.
+pub fn create_backup(config: &Config, tasks: &[Task]) -> color_eyre::Result<Backup> {
+    let dir = Path::new(&config.backup.dir);
+    fs::create_dir_all(dir).wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+    let mut created = Local::now().trunc_subsecs(6);
+    let mut path;
+    loop {
+        let file_name = format!("{}{}.json", FILE_PREFIX, created.format(TIMESTAMP_FORMAT));
+        path = dir.join(file_name);
+        if !path.exists() {
+            break;
+        }
+        created += TimeDelta::microseconds(1);
+    }
+    storage::write_json(&path, tasks)?;
+    for old in list_backups(config)?.iter().skip(config.backup.keep.max(1)) {
+        fs::remove_file(&old.path)
+            .wrap_err_with(|| format!("Failed to remove {}", old.path.display()))?;
+    }
+    Ok(Backup { path, created })
+}
+
+/// Reads the task tree saved in a backup.
+pub fn load_backup(path: &Path) -> color_eyre::Result<Vec<Task>> {
+    storage::read_json(path)
+}
+
+/// The `restore-backup` command: lists the backups, or restores the one given by its number
+/// in that list or by its path. The current data is backed up first.
+pub fn restore_command(config: &Config, which: Option<&str>) -> color_eyre::Result<()> {
+    let backups = list_backups(config)?;
+    let Some(which) = which else {
+        if backups.is_empty() {
+            println!("No backups in {}", config.backup.dir);
+        }
+        for (number, backup) in backups.iter().enumerate() {
+            let task_count = load_backup(&backup.path).map_or(0, |tasks| tasks.len());
+            println!(
+                "{:>3}  {}  {} top-level task(s)",
+                number + 1,
+                backup.created.format("%Y-%m-%d %H:%M:%S"),
+                task_count
+            );
+        }
+        println!("\nRun `vinegar restore-backup <number>` to restore one.");
+        return Ok(());
+    };
+    let path = match which.parse::<usize>() {
+        Ok(number) => backups
+            .get(number.wrapping_sub(1))
+            .map(|backup| backup.path.clone())
+            .ok_or_else(|| eyre!("There is no backup number {}", number))?,
+        Err(_) => PathBuf::from(which),
+    };
+    let tasks = load_backup(&path)?;
+    let current = storage::load_tasks(config)?;
+    if !current.is_empty() {
+        let saved = create_backup(config, &current)?;
+        println!("Saved the current tasks to {}", saved.path.display());
+    }
+    storage::save_tasks(config, &tasks)?;
+    println!("Restored {} from {}", config.storage.path, path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        let dir = std::env::temp_dir().join(format!("vinegar-backup-{}", uuid::Uuid::new_v4()));
+        let mut config = Config::default();
+        config.backup.dir = dir.display().to_string();
+        config.backup.keep = 10;
+        config
+    }
+
+    #[test]
+    fn backups_made_in_quick_succession_are_all_kept() {
+        let config = config();
+        let tasks = vec![Task::new("Water plants".to_string())];
+        let created: Vec<Backup> = (0..3)
+            .map(|_| create_backup(&config, &tasks).unwrap())
+            .collect();
+
+        let listed = list_backups(&config).unwrap();
+
+        assert_eq!(listed.len(), 3);
+        assert_eq!(listed[0], created[2]);
+    }
+}
@@ -0,0 +1,1002 @@
+use crate::app::clipboard::base64_encode;
+use crate::app::flat::{FlatOp, FlatTask, FlatTree};
+use crate::app::task::TaskId;
+use crate::config::CalDavConfig;
+use crate::ical::{self, Todo};
+use color_eyre::eyre::{bail, eyre, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use uuid::Uuid;
+
+/// Longest a single request to the server may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A to-do as stored in the collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteTodo {
+    /// Path of the calendar object resource holding the to-do.
+    pub href: String,
+    pub etag: String,
+    pub todo: Todo,
+}
+
+/// What writing a to-do to the collection came to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Written {
+    /// Stored at `href`, now tagged `etag`; empty if the server did not say.
+    Stored { href: String, etag: String },
+    /// The resource changed or appeared on the server since its ETag was read.
+    Conflict,
+}
+
+/// A collection of to-dos [`sync`] reads and writes. [`CalDavClient`] talks to a CalDAV
+/// server; anything else that keeps ETags the same way can stand in for one.
+pub trait CalendarStore {
+    /// Every to-do in the collection.
+    fn list(&self) -> impl Future<Output = color_eyre::Result<Vec<RemoteTodo>>> + Send;
+
+    /// Creates the to-do if `existing` is `None`, or replaces the resource at its href if it
+    /// still has its ETag.
+    fn put(
+        &self,
+        todo: &Todo,
+        existing: Option<(&str, &str)>,
+    ) -> impl Future<Output = color_eyre::Result<Written>> + Send;
+
+    /// Removes the resource at `href` if it still has `etag`. Returns `false` on a conflict.
+    fn delete(
+        &self,
+        href: &str,
+        etag: &str,
+    ) -> impl Future<Output = color_eyre::Result<bool>> + Send;
+}
+
+/// The to-do a task was last synced as, to tell which side changed since.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncedTodo {
+    pub href: String,
+    pub etag: String,
+    pub todo: Todo,
+}
+
+/// Every synced task by ID, kept between runs.
+pub type SyncState = HashMap<TaskId, SyncedTodo>;
+
+/// A change the server asks of the local tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalChange {
+    /// The task as it was when the sync started. If it changed meanwhile the change is
+    /// dropped, and `previous` is put back so the next run merges the two.
+    pub seen: Option<FlatTask>,
+    pub op: FlatOp,
+    pub previous: Option<SyncedTodo>,
+}
+
+/// The result of one sync run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncOutcome {
+    pub changes: Vec<LocalChange>,
+    pub state: SyncState,
+    /// Tasks written to or removed from the server.
+    pub pushed: usize,
+    /// Tasks changed, added or removed locally.
+    pub pulled: usize,
+    /// Tasks changed on both sides in the same fields, where the local version was kept, or
+    /// whose resource changed on the server while it was being written.
+    pub conflicts: usize,
+}
+
+/// Where one task stands on each side.
+enum Plan {
+    /// Nothing to do beyond remembering the server's version.
+    Keep(RemoteTodo),
+    /// Write the local version, replacing the resource given.
+    Push(Todo, Option<RemoteTodo>),
+    /// Take the server's version into the tree.
+    Pull(RemoteTodo),
+    /// Write `merged` to the server and into the tree.
+    Merge(Todo, RemoteTodo),
+    /// Remove the task from the server.
+    DeleteRemote(RemoteTodo),
+    /// Remove the task from the tree.
+    DeleteLocal,
+    Forget,
+}
+
+/// Syncs `tree` with the collection in both directions, starting from what was synced last
+/// time. Changes on one side are copied to the other; tasks changed on both are merged field
+/// by field, keeping the local value where both changed the same one. A task deleted on one
+/// side and changed on the other is kept. Writes only go through if the resource still has
+/// the ETag it was read with, so changes made on the server mid-sync are never overwritten.
+pub async fn sync(
+    store: &impl CalendarStore,
+    tree: FlatTree,
+    mut state: SyncState,
+) -> color_eyre::Result<SyncOutcome> {
+    let remote = store.list().await?;
+
+    // Match the server's UIDs to task IDs, keeping those synced before
+    let mut ids_by_uid: HashMap<String, TaskId> = state
+        .iter()
+        .map(|(&id, synced)| (synced.todo.uid.clone(), id))
+        .collect();
+    let mut remote_by_id: HashMap<TaskId, RemoteTodo> = HashMap::new();
+    for entry in remote {
+        let id = match ids_by_uid.get(&entry.todo.uid) {
+            Some(&id) => id,
+            None => {
+                let id = Uuid::parse_str(&entry.todo.uid)
+                    .ok()
+                    .filter(|id| !state.contains_key(id) && !remote_by_id.contains_key(id))
+                    .unwrap_or_else(Uuid::new_v4);
+                ids_by_uid.insert(entry.todo.uid.clone(), id);
+                id
+            }
+        };
+        remote_by_id.insert(id, entry);
+    }
+    let uid_of = |id: TaskId| -> String {
+        state
+            .get(&id)
+            .map(|synced| synced.todo.uid.clone())
+            .or_else(|| remote_by_id.get(&id).map(|entry| entry.todo.uid.clone()))
+            .unwrap_or_else(|| id.to_string())
+    };
+    let local: HashMap<TaskId, Todo> = tree
+        .values()
+        .map(|entry| {
+            let parent = entry.parent.map(uid_of);
+            let todo = Todo::from_task(&entry.task, uid_of(entry.task.id), parent);
+            (entry.task.id, todo)
+        })
+        .collect();
+
+    let mut ids: Vec<TaskId> = local.keys().chain(remote_by_id.keys()).copied().collect();
+    ids.extend(state.keys().copied());
+    ids.sort();
+    ids.dedup();
+    let mut conflicts = 0;
+    let mut plans: HashMap<TaskId, Plan> = HashMap::new();
+    for id in ids {
+        let base = state.get(&id);
+        let plan = match (local.get(&id), remote_by_id.remove(&id)) {
+            (Some(todo), Some(entry)) => match base {
+                Some(base) => {
+                    let local_changed = !todo.same_content(&base.todo);
+                    let remote_changed =
+                        entry.etag != base.etag && !entry.todo.same_content(&base.todo);
+                    match (local_changed, remote_changed) {
+                        (false, false) => Plan::Keep(entry),
+                        (true, false) => Plan::Push(todo.clone(), Some(entry)),
+                        (false, true) => Plan::Pull(entry),
+                        (true, true) => {
+                            let (merged, clashed) = merge_todos(&base.todo, todo, &entry.todo);
+                            conflicts += usize::from(clashed);
+                            Plan::Merge(merged, entry)
+                        }
+                    }
+                }
+                // Seen on both sides but never synced, e.g. after the state was lost
+                None if todo.same_content(&entry.todo) => Plan::Keep(entry),
+                None => {
+                    conflicts += 1;
+                    Plan::Push(todo.clone(), Some(entry))
+                }
+            },
+            (Some(todo), None) => match base {
+                Some(base) if todo.same_content(&base.todo) => Plan::DeleteLocal,
+                _ => Plan::Push(todo.clone(), None),
+            },
+            (None, Some(entry)) => match base {
+                Some(base) if entry.etag == base.etag || entry.todo.same_content(&base.todo) => {
+                    Plan::DeleteRemote(entry)
+                }
+                _ => Plan::Pull(entry),
+            },
+            (None, None) => Plan::Forget,
+        };
+        plans.insert(id, plan);
+    }
+
+    // Deleting a task deletes its subtree, so keep any task with a subtask that stays
+    let mut kept = true;
+    while kept {
+        kept = false;
+        for entry in tree.values() {
+            let Some(parent) = entry.parent else {
+                continue;
+            };
+            let child_stays = !matches!(plans.get(&entry.task.id), Some(Plan::DeleteLocal));
+            if child_stays && matches!(plans.get(&parent), Some(Plan::DeleteLocal)) {
+                plans.insert(parent, Plan::Push(local[&parent].clone(), None));
+                kept = true;
+            }
+        }
+    }
+
+    let mut outcome = SyncOutcome {
+        changes: Vec::new(),
+        state: SyncState::new(),
+        pushed: 0,
+        pulled: 0,
+        conflicts,
+    };
+    let parent_id = |todo: &Todo| {
+        todo.parent
+            .as_ref()
+            .and_then(|uid| ids_by_uid.get(uid).copied())
+    };
+    for (id, plan) in plans {
+        let previous = state.remove(&id);
+        let seen = tree.get(&id).cloned();
+        let pull = |todo: &Todo, outcome: &mut SyncOutcome| {
+            let entry = match &seen {
+                Some(seen) => {
+                    let mut task = seen.task.clone();
+                    todo.apply_to(&mut task);
+                    let parent = parent_id(todo);
+                    let position = if parent == seen.parent {
+                        seen.position
+                    } else {
+                        usize::MAX
+                    };
+                    FlatTask {
+                        task,
+                        parent,
+                        position,
+                    }
+                }
+                None => FlatTask {
+                    task: todo.to_task(id),
+                    parent: parent_id(todo),
+                    position: usize::MAX,
+                },
+            };
+            outcome.changes.push(LocalChange {
+                seen: seen.clone(),
+                op: FlatOp::Put(Box::new(entry)),
+                previous: previous.clone(),
+            });
+            outcome.pulled += 1;
+        };
+        match plan {
+            Plan::Keep(entry) => {
+                outcome.state.insert(id, synced(entry));
+            }
+            Plan::Push(todo, existing) => {
+                let existing_ref = existing
+                    .as_ref()
+                    .map(|e| (e.href.as_str(), e.etag.as_str()));
+                match store.put(&todo, existing_ref).await? {
+                    Written::Stored { href, etag } => {
+                        outcome.state.insert(id, SyncedTodo { href, etag, todo });
+                        outcome.pushed += 1;
+                    }
+                    Written::Conflict => {
+                        // Leave it to the next run, which sees the server's new version
+                        outcome
+                            .state
+                            .extend(previous.map(|previous| (id, previous)));
+                        outcome.conflicts += 1;
+                    }
+                }
+            }
+            Plan::Pull(entry) => {
+                pull(&entry.todo, &mut outcome);
+                outcome.state.insert(id, synced(entry));
+            }
+            Plan::Merge(merged, entry) => {
+                if merged.same_content(&entry.todo) {
+                    pull(&merged, &mut outcome);
+                    outcome.state.insert(id, synced(entry));
+                    continue;
+                }
+                let existing = Some((entry.href.as_str(), entry.etag.as_str()));
+                match store.put(&merged, existing).await? {
+                    Written::Stored { href, etag } => {
+                        if !merged.same_content(&local[&id]) {
+                            pull(&merged, &mut outcome);
+                        }
+                        outcome.pushed += 1;
+                        let todo = merged;
+                        outcome.state.insert(id, SyncedTodo { href, etag, todo });
+                    }
+                    Written::Conflict => {
+                        outcome
+                            .state
+                            .extend(previous.map(|previous| (id, previous)));
+                        outcome.conflicts += 1;
+                    }
+                }
+            }
+            Plan::DeleteRemote(entry) => {
+                if store.delete(&entry.href, &entry.etag).await? {
+                    outcome.pushed += 1;
+                } else {
+                    outcome
+                        .state
+                        .extend(previous.map(|previous| (id, previous)));
+                    outcome.conflicts += 1;
+                }
+            }
+            Plan::DeleteLocal => {
+                outcome.changes.push(LocalChange {
+                    seen,
+                    op: FlatOp::Remove(id),
+                    previous,
+                });
+                outcome.pulled += 1;
+            }
+            Plan::Forget => {}
+        }
+    }
+    Ok(outcome)
+}
+
+fn synced(entry: RemoteTodo) -> SyncedTodo {
+    SyncedTodo {
+        href: entry.href,
+        etag: entry.etag,
+        todo: entry.todo,
+    }
+}
+
+/// Merges two versions of a to-do field by field against their common base. Where both
+/// changed the same field the local value wins, which the returned flag reports.
+fn merge_todos(base: &Todo, local: &Todo, remote: &Todo) -> (Todo, bool) {
+    let mut merged = local.clone();
+    let mut clashed = false;
+    macro_rules! merge_field {
+        ($($field:ident),*) => {$(
+            if local.$field == base.$field {
+                merged.$field = remote.$field.clone();
+            } else if remote.$field != base.$field && remote.$field != local.$field {
+                clashed = true;
+            }
+        )*};
+    }
+    merge_field!(summary, due, priority, categories, parent);
+    if local.completed == base.completed {
+        merged.completed = remote.completed;
+        merged.completed_at = remote.completed_at;
+    } else if remote.completed != base.completed && remote.completed != local.completed {
+        clashed = true;
+    }
+    merged.last_modified = local.last_modified.max(remote.last_modified);
+    (merged, clashed)
+}
+
+/// A CalDAV calendar collection, reached over plain HTTP.
+#[derive(Debug, Clone)]
+pub struct CalDavClient {
+    host: String,
+    port: u16,
+    /// Path of the collection, ending in `/`.
+    path: String,
+    authorization: Option<String>,
+}
+
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl CalDavClient {
+    /// A client for the collection configured in `[caldav]`.
+    pub fn new(config: &CalDavConfig) -> color_eyre::Result<Self> {
+        let url = config.url.trim();
+        let Some(rest) = url.strip_prefix("http://") else {
+            if url.starts_with("https://") {
+                bail!(
+                    "HTTPS is not supported; reach the server through a local proxy over http://"
+                );
+            }
+            bail!("CalDAV url must start with http://, got '{}'", url);
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .wrap_err_with(|| format!("Bad port in CalDAV url '{}'", url))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            bail!("CalDAV url '{}' has no host", url);
+        }
+        // Basic credentials travel in cleartext, so they may only go to this machine
+        if !config.username.is_empty() && !is_loopback(host) {
+            bail!(
+                "Refusing to send CalDAV credentials over plain HTTP to {}; \
+                 reach the server through a TLS proxy on localhost",
+                host
+            );
+        }
+        let mut path = path.to_string();
+        if !path.ends_with('/') {
+            path.push('/');
+        }
+        let authorization = (!config.username.is_empty()).then(|| {
+            let credentials = format!("{}:{}", config.username, config.password);
+            format!("Basic {}", base64_encode(credentials.as_bytes()))
+        });
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path,
+            authorization,
+        })
+    }
+
+    async fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> color_eyre::Result<Response> {
+        tokio::time::timeout(REQUEST_TIMEOUT, self.send(method, path, headers, body))
+            .await
+            .map_err(|_| eyre!("{} {} timed out", method, path))?
+    }
+
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> color_eyre::Result<Response> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .wrap_err_with(|| format!("Could not connect to {}:{}", self.host, self.port))?;
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            method,
+            path,
+            self.host,
+            self.port,
+            body.len()
+        );
+        if let Some(authorization) = &self.authorization {
+            head.push_str(&format!("Authorization: {}\r\n", authorization));
+        }
+        for (name, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body.as_bytes()).await?;
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw).await?;
+        parse_response(&raw)
+    }
+
+    /// The path a resource href points at, which servers may give as a full URL.
+    fn resource_path(href: &str) -> &str {
+        match href.split_once("://") {
+            Some((_, rest)) => rest.find('/').map_or("/", |slash| &rest[slash..]),
+            None => href,
+        }
+    }
+}
+
+impl CalendarStore for CalDavClient {
+    async fn list(&self) -> color_eyre::Result<Vec<RemoteTodo>> {
+        let query = concat!(
+            r#"<?xml version="1.0" encoding="utf-8"?>"#,
+            r#"<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">"#,
+            r#"<d:prop><d:getetag/><c:calendar-data/></d:prop>"#,
+            r#"<c:filter><c:comp-filter name="VCALENDAR">"#,
+            r#"<c:comp-filter name="VTODO"/>"#,
+            r#"</c:comp-filter></c:filter></c:calendar-query>"#
+        );
+        let headers = [
+            ("Depth", "1"),
+            ("Content-Type", "application/xml; charset=utf-8"),
+        ];
+        let response = self.request("REPORT", &self.path, &headers, query).await?;
+        if response.status != 207 {
+            bail!("Listing {} failed with HTTP {}", self.path, response.status);
+        }
+        let mut todos = Vec::new();
+        for item in xml_elements(&response.body, "response") {
+            let (Some(href), Some(data)) = (
+                xml_elements(item, "href")
+                    .first()
+                    .map(|href| xml_text(href)),
+                xml_elements(item, "calendar-data")
+                    .first()
+                    .map(|data| xml_text(data)),
+            ) else {
+                continue;
+            };
+            let etag = xml_elements(item, "getetag")
+                .first()
+                .map(|etag| xml_text(etag))
+                .unwrap_or_default();
+            if let Some(todo) = ical::parse_calendar(&data).into_iter().next() {
+                todos.push(RemoteTodo { href, etag, todo });
+            }
+        }
+        Ok(todos)
+    }
+
+    async fn put(
+        &self,
+        todo: &Todo,
+        existing: Option<(&str, &str)>,
+    ) -> color_eyre::Result<Written> {
+        let (href, condition) = match existing {
+            Some((href, etag)) => (href.to_string(), ("If-Match", etag)),
+            None => {
+                let name: String = todo
+                    .uid
+                    .chars()
+                    .map(|c| {
+                        if c.is_ascii_alphanumeric() || c == '-' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                (format!("{}{}.ics", self.path, name), ("If-None-Match", "*"))
+            }
+        };
+        let path = Self::resource_path(&href);
+        let mut headers = vec![("Content-Type", "text/calendar; charset=utf-8")];
+        if !condition.1.is_empty() {
+            headers.push(condition);
+        }
+        let body = ical::write_calendar(std::slice::from_ref(todo));
+        let response = self.request("PUT", path, &headers, &body).await?;
+        match response.status {
+            200 | 201 | 204 => Ok(Written::Stored {
+                etag: response.header("ETag").unwrap_or_default().to_string(),
+                href,
+            }),
+            412 => Ok(Written::Conflict),
+            status => bail!("Writing {} failed with HTTP {}", path, status),
+        }
+    }
+
+    async fn delete(&self, href: &str, etag: &str) -> color_eyre::Result<bool> {
+        let path = Self::resource_path(href);
+        let headers: Vec<(&str, &str)> = if etag.is_empty() {
+            Vec::new()
+        } else {
+            vec![("If-Match", etag)]
+        };
+        let response = self.request("DELETE", path, &headers, "").await?;
+        match response.status {
+            200 | 204 | 404 => Ok(true),
+            412 => Ok(false),
+            status => bail!("Deleting {} failed with HTTP {}", path, status),
+        }
+    }
+}
+
+/// Whether `host` names this machine, so traffic to it never leaves it.
+fn is_loopback(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|address| address.is_loopback())
+}
+
+fn parse_response(raw: &[u8]) -> color_eyre::Result<Response> {
+    let split = raw
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| eyre!("Malformed HTTP response"))?;
+    let head = String::from_utf8_lossy(&raw[..split]);
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| eyre!("Malformed HTTP status line"))?;
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let mut response = Response {
+        status,
+        headers,
+        body: String::new(),
+    };
+    let body = &raw[split + 4..];
+    let chunked = response
+        .header("Transfer-Encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+    let body = if chunked {
+        decode_chunked(body)?
+    } else {
+        match response.header("Content-Length").map(str::parse::<usize>) {
+            Some(Ok(length)) => body[..length.min(body.len())].to_vec(),
+            _ => body.to_vec(),
+        }
+    };
+    response.body = String::from_utf8_lossy(&body).into_owned();
+    Ok(response)
+}
+
+fn decode_chunked(mut body: &[u8]) -> color_eyre::Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(|| eyre!("Malformed chunked body"))?;
+        let size_line = String::from_utf8_lossy(&body[..line_end]);
+        let size_hex = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_hex, 16)
+            .wrap_err_with(|| format!("Bad chunk size '{}'", size_hex))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(decoded);
+        }
+        if body.len() < size {
+            bail!("Truncated chunked body");
+        }
+        decoded.extend_from_slice(&body[..size]);
+        body = body.get(size + 2..).unwrap_or_default();
+    }
+}
+
+/// The contents of every element called `name` in any namespace, such as `d:href` for `href`.
+/// Elements of the same name nested in each other are not supported.
+fn xml_elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let tag_end = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let tag = &rest[..tag_end];
+        let local_name = tag.rsplit(':').next().unwrap_or(tag);
+        if local_name != name {
+            continue;
+        }
+        let Some(close) = rest.find('>') else {
+            break;
+        };
+        if rest[..close].ends_with('/') {
+            found.push("");
+            rest = &rest[close + 1..];
+            continue;
+        }
+        let content = &rest[close + 1..];
+        let end_tag = format!("</{}", tag);
+        let Some(end) = content.find(&end_tag) else {
+            break;
+        };
+        found.push(&content[..end]);
+        rest = &content[end..];
+    }
+    found
+}
+
+/// The text of an element's contents, with entities and CDATA sections decoded.
+fn xml_text(content: &str) -> String {
+    let content = content.trim();
+    if let Some(data) = content
+        .strip_prefix("<![CDATA[")
+        .and_then(|data| data.strip_suffix("]]>"))
+    {
+        return data.to_string();
+    }
+    let mut text = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(amp) = rest.find('&') {
+        text.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semicolon) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..semicolon];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                text.push(c);
+                rest = &rest[semicolon + 1..];
+            }
+            None => {
+                text.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::flat;
+    use crate::app::task::{Priority, Task};
+    use std::sync::Mutex;
+
+    /// A collection kept in memory that checks ETags the way a CalDAV server does.
+    #[derive(Default)]
+    struct MemoryStore {
+        /// Every to-do with its ETag, by href.
+        resources: Mutex<HashMap<String, (String, Todo)>>,
+        last_etag: Mutex<usize>,
+        /// Stored just before the next write, like another client racing the sync.
+        racing_write: Mutex<Option<Todo>>,
+    }
+
+    impl MemoryStore {
+        fn store(&self, todo: Todo) -> (String, String) {
+            let mut last_etag = self.last_etag.lock().unwrap();
+            *last_etag += 1;
+            let etag = format!("\"{}\"", last_etag);
+            let href = format!("/tasks/{}.ics", todo.uid);
+            let resource = (etag.clone(), todo);
+            self.resources
+                .lock()
+                .unwrap()
+                .insert(href.clone(), resource);
+            (href, etag)
+        }
+
+        fn todo(&self, uid: &str) -> Option<Todo> {
+            let resources = self.resources.lock().unwrap();
+            resources
+                .values()
+                .map(|(_, todo)| todo)
+                .find(|todo| todo.uid == uid)
+                .cloned()
+        }
+    }
+
+    impl CalendarStore for MemoryStore {
+        async fn list(&self) -> color_eyre::Result<Vec<RemoteTodo>> {
+            let resources = self.resources.lock().unwrap();
+            Ok(resources
+                .iter()
+                .map(|(href, (etag, todo))| RemoteTodo {
+                    href: href.clone(),
+                    etag: etag.clone(),
+                    todo: todo.clone(),
+                })
+                .collect())
+        }
+
+        async fn put(
+            &self,
+            todo: &Todo,
+            existing: Option<(&str, &str)>,
+        ) -> color_eyre::Result<Written> {
+            if let Some(racing) = self.racing_write.lock().unwrap().take() {
+                self.store(racing);
+            }
+            let href = format!("/tasks/{}.ics", todo.uid);
+            let current = self.resources.lock().unwrap().get(&href).cloned();
+            let matches = match (existing, current) {
+                (Some((_, etag)), Some((current, _))) => etag == current,
+                (None, None) => true,
+                _ => false,
+            };
+            if !matches {
+                return Ok(Written::Conflict);
+            }
+            let (href, etag) = self.store(todo.clone());
+            Ok(Written::Stored { href, etag })
+        }
+
+        async fn delete(&self, href: &str, etag: &str) -> color_eyre::Result<bool> {
+            let mut resources = self.resources.lock().unwrap();
+            if resources
+                .get(href)
+                .is_some_and(|(current, _)| current != etag)
+            {
+                return Ok(false);
+            }
+            resources.remove(href);
+            Ok(true)
+        }
+    }
+
+    /// Syncs and applies the outcome the way the app does when nothing changed meanwhile.
+    async fn sync_tree(
+        store: &MemoryStore,
+        tree: &mut FlatTree,
+        state: SyncState,
+    ) -> (SyncState, SyncOutcome) {
+        let outcome = sync(store, tree.clone(), state).await.unwrap();
+        flat::apply(tree, outcome.changes.iter().map(|change| change.op.clone()));
+        (outcome.state.clone(), outcome)
+    }
+
+    fn remote_todo(uid: &str, summary: &str) -> Todo {
+        let mut todo = Todo::from_task(&Task::new(summary.to_string()), uid.to_string(), None);
+        todo.created = None;
+        todo
+    }
+
+    #[tokio::test]
+    async fn pushes_new_tasks() {
+        let store = MemoryStore::default();
+        let task = Task::new("Buy milk".to_string());
+        let mut tree = flat::flatten(std::slice::from_ref(&task));
+
+        let (state, outcome) = sync_tree(&store, &mut tree, SyncState::new()).await;
+
+        assert_eq!(outcome.pushed, 1);
+        assert!(outcome.changes.is_empty());
+        assert_eq!(
+            store.todo(&task.id.to_string()).unwrap().summary,
+            "Buy milk"
+        );
+        assert!(state.contains_key(&task.id));
+    }
+
+    #[tokio::test]
+    async fn pulls_new_todos() {
+        let store = MemoryStore::default();
+        store.store(remote_todo("made-elsewhere", "Call the bank"));
+        let mut tree = FlatTree::new();
+
+        let (state, outcome) = sync_tree(&store, &mut tree, SyncState::new()).await;
+
+        assert_eq!(outcome.pulled, 1);
+        let entry = tree.values().next().unwrap();
+        assert_eq!(entry.task.name, "Call the bank");
+        assert_eq!(state[&entry.task.id].todo.uid, "made-elsewhere");
+    }
+
+    #[tokio::test]
+    async fn merges_fields_changed_on_each_side() {
+        let store = MemoryStore::default();
+        let task = Task::new("Buy milk".to_string());
+        let uid = task.id.to_string();
+        let mut tree = flat::flatten(std::slice::from_ref(&task));
+        let (state, _) = sync_tree(&store, &mut tree, SyncState::new()).await;
+
+        tree.get_mut(&task.id).unwrap().task.tags = vec!["errand".to_string()];
+        let mut remote = store.todo(&uid).unwrap();
+        remote.priority = Some(Priority::High);
+        store.store(remote);
+        let (_, outcome) = sync_tree(&store, &mut tree, state).await;
+
+        assert_eq!(outcome.conflicts, 0);
+        for todo in [
+            Todo::from_task(&tree[&task.id].task, uid.clone(), None),
+            store.todo(&uid).unwrap(),
+        ] {
+            assert_eq!(todo.categories, vec!["errand".to_string()]);
+            assert_eq!(todo.priority, Some(Priority::High));
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_the_local_value_where_both_changed_a_field() {
+        let store = MemoryStore::default();
+        let task = Task::new("Buy milk".to_string());
+        let uid = task.id.to_string();
+        let mut tree = flat::flatten(std::slice::from_ref(&task));
+        let (state, _) = sync_tree(&store, &mut tree, SyncState::new()).await;
+
+        tree.get_mut(&task.id).unwrap().task.name = "Buy oat milk".to_string();
+        let mut remote = store.todo(&uid).unwrap();
+        remote.summary = "Buy soy milk".to_string();
+        store.store(remote);
+        let (_, outcome) = sync_tree(&store, &mut tree, state).await;
+
+        assert_eq!(outcome.conflicts, 1);
+        assert_eq!(tree[&task.id].task.name, "Buy oat milk");
+        assert_eq!(store.todo(&uid).unwrap().summary, "Buy oat milk");
+    }
+
+    #[tokio::test]
+    async fn retries_a_write_the_server_refused() {
+        let store = MemoryStore::default();
+        let task = Task::new("Buy milk".to_string());
+        let uid = task.id.to_string();
+        let mut tree = flat::flatten(std::slice::from_ref(&task));
+        let (state, _) = sync_tree(&store, &mut tree, SyncState::new()).await;
+        let synced = state[&task.id].clone();
+
+        tree.get_mut(&task.id).unwrap().task.name = "Buy oat milk".to_string();
+        let mut racing = store.todo(&uid).unwrap();
+        racing.priority = Some(Priority::Low);
+        *store.racing_write.lock().unwrap() = Some(racing);
+        let (state, outcome) = sync_tree(&store, &mut tree, state).await;
+
+        assert_eq!(outcome.conflicts, 1);
+        assert_eq!(state[&task.id], synced);
+        assert_eq!(store.todo(&uid).unwrap().summary, "Buy milk");
+
+        let (_, outcome) = sync_tree(&store, &mut tree, state).await;
+
+        assert_eq!(outcome.conflicts, 0);
+        let remote = store.todo(&uid).unwrap();
+        assert_eq!(remote.summary, "Buy oat milk");
+        assert_eq!(remote.priority, Some(Priority::Low));
+        assert_eq!(tree[&task.id].task.priority, Some(Priority::Low));
+    }
+
+    #[tokio::test]
+    async fn removes_tasks_deleted_on_the_server() {
+        let store = MemoryStore::default();
+        let task = Task::new("Buy milk".to_string());
+        let mut tree = flat::flatten(std::slice::from_ref(&task));
+        let (state, _) = sync_tree(&store, &mut tree, SyncState::new()).await;
+
+        store.resources.lock().unwrap().clear();
+        let (state, outcome) = sync_tree(&store, &mut tree, state).await;
+
+        assert_eq!(outcome.pulled, 1);
+        assert!(tree.is_empty());
+        assert!(state.is_empty());
+    }
+
+    #[tokio::test]
+    async fn deletes_todos_of_tasks_removed_locally() {
+        let store = MemoryStore::default();
+        let task = Task::new("Buy milk".to_string());
+        let mut tree = flat::flatten(std::slice::from_ref(&task));
+        let (state, _) = sync_tree(&store, &mut tree, SyncState::new()).await;
+
+        tree.clear();
+        let (state, outcome) = sync_tree(&store, &mut tree, state).await;
+
+        assert_eq!(outcome.pushed, 1);
+        assert!(store.resources.lock().unwrap().is_empty());
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn only_sends_credentials_to_this_machine() {
+        let config = |url: &str| CalDavConfig {
+            url: url.to_string(),
+            username: "alice".to_string(),
+            password: "secret".to_string(),
+            ..CalDavConfig::default()
+        };
+        assert!(CalDavClient::new(&config("http://localhost:5232/alice/tasks/")).is_ok());
+        assert!(CalDavClient::new(&config("http://127.0.0.1:5232/alice/tasks/")).is_ok());
+        assert!(CalDavClient::new(&config("http://dav.example.com/alice/tasks/")).is_err());
+        let anonymous = CalDavConfig {
+            username: String::new(),
+            ..config("http://dav.example.com/tasks/")
+        };
+        assert!(CalDavClient::new(&anonymous).is_ok());
+    }
+}
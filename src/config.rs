@@ -11,9 +11,36 @@ pub struct Config {
     pub layout: LayoutConfig,
     #[serde(default)]
     pub theme: ThemeConfig,
+    #[serde(default)]
+    pub confirm: ConfirmConfig,
+    #[serde(default)]
+    pub export: ExportConfig,
+    #[serde(default)]
+    pub clipboard: ClipboardConfig,
+    #[serde(default)]
+    pub view: ViewConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub pomodoro: PomodoroConfig,
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+    #[serde(default)]
+    pub trash: TrashConfig,
+    #[serde(default)]
+    pub backup: BackupConfig,
+    #[serde(default)]
+    pub git: GitConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub control: ControlConfig,
+    #[serde(default)]
+    pub caldav: CalDavConfig,
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
 pub struct Keybindings {
     pub quit: KeyEvent,
     pub add_task: KeyEvent,
@@ -23,6 +50,43 @@ pub struct Keybindings {
     pub toggle_expand: KeyEvent,
     pub select_next: KeyEvent,
     pub select_previous: KeyEvent,
+    pub toggle_mark: KeyEvent,
+    pub visual_mode: KeyEvent,
+    pub clear_marks: KeyEvent,
+    pub toggle_complete: KeyEvent,
+    pub move_marked: KeyEvent,
+    pub tag_task: KeyEvent,
+    pub export_tasks: KeyEvent,
+    pub yank_task: KeyEvent,
+    pub cut_task: KeyEvent,
+    pub paste_sibling: KeyEvent,
+    pub paste_child: KeyEvent,
+    pub cycle_sort: KeyEvent,
+    pub sort_siblings: KeyEvent,
+    pub cycle_group: KeyEvent,
+    pub zoom_in: KeyEvent,
+    pub zoom_out: KeyEvent,
+    pub expand_subtree: KeyEvent,
+    pub collapse_subtree: KeyEvent,
+    pub expand_all: KeyEvent,
+    pub collapse_all: KeyEvent,
+    pub expand_to_depth: KeyEvent,
+    pub toggle_details: KeyEvent,
+    pub block_task: KeyEvent,
+    pub unblock_task: KeyEvent,
+    pub next_actionable: KeyEvent,
+    pub toggle_timer: KeyEvent,
+    pub toggle_pomodoro: KeyEvent,
+    pub archive_tasks: KeyEvent,
+    pub archive_completed: KeyEvent,
+    pub show_archive: KeyEvent,
+    pub restore_task: KeyEvent,
+    pub show_trash: KeyEvent,
+    pub show_backups: KeyEvent,
+    pub show_history: KeyEvent,
+    pub keep_ours: KeyEvent,
+    pub keep_theirs: KeyEvent,
+    pub sync_calendar: KeyEvent,
 }
 
 #[derive(Debug, Deserialize)]
@@ -37,12 +101,155 @@ pub enum LayoutDirection {
     Vertical,
 }
 
+/// How the task list is ordered and grouped when vinegar starts.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ViewConfig {
+    pub sort: Option<SortKey>,
+    pub group: Option<GroupKey>,
+    /// Restore which tasks were expanded last session, instead of starting fully collapsed.
+    pub remember_expanded: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// File the task tree is loaded from at startup and saved to on quit.
+    pub path: String,
+    /// Rewrite the data file and start a fresh journal after this many journaled changes.
+    pub snapshot_every: usize,
+    /// Save the data file this many seconds after the last change, and look for changes other
+    /// instances saved to it just as often; 0 only saves on quit and never looks.
+    pub sync_seconds: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// File archived tasks are kept in, apart from the active tree.
+    pub path: String,
+    /// Archiving all completed tasks only takes those completed at least this many days ago.
+    pub older_than_days: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct TrashConfig {
+    /// File deleted tasks are kept in until they are restored or purged.
+    pub path: String,
+    /// Purge deleted tasks after this many days; 0 keeps them forever.
+    pub retention_days: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Directory the timestamped copies of the data file are written to.
+    pub dir: String,
+    /// How often a new backup is taken while tasks are being changed.
+    pub interval: BackupInterval,
+    /// Number of backups to keep; older ones are removed.
+    pub keep: usize,
+}
+
+/// Versioning of the data file in a local git repository.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct GitConfig {
+    /// Commit the data file to a repository of its own, `.vinegar-git` in its directory,
+    /// creating it if needed.
+    pub enabled: bool,
+    /// Changes made within this many seconds of each other go into the same commit.
+    pub batch_seconds: u64,
+}
+
+/// The local HTTP/JSON API for editor plugins and dashboards.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Serve the API while vinegar runs; `--api` on the command line does the same.
+    pub enabled: bool,
+    /// Port to listen on. Only connections from this machine are accepted.
+    pub port: u16,
+    /// Secret every request must carry as `Authorization: Bearer <token>`, so web pages
+    /// open in a browser cannot drive the API. The API does not start without one.
+    pub token: String,
+}
+
+/// The Unix socket other programs drive a running instance through.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ControlConfig {
+    /// Listen for commands on the socket while vinegar runs.
+    pub enabled: bool,
+    /// Path of the socket, also used by `vinegar remote`.
+    pub socket: String,
+}
+
+/// Two-way sync of the tree with the to-dos in a CalDAV calendar collection.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct CalDavConfig {
+    /// Sync when vinegar starts and every `interval_seconds` after.
+    pub enabled: bool,
+    /// The collection's URL, such as `http://localhost:5232/alice/tasks/`. Only plain HTTP is
+    /// spoken, so reach remote servers through a local TLS proxy.
+    pub url: String,
+    /// User name for basic authentication; left empty, requests are sent without it.
+    /// Credentials are sent in cleartext, so they are refused unless the host is loopback.
+    pub username: String,
+    pub password: String,
+    /// Seconds between syncs; 0 only syncs on start and when asked to.
+    pub interval_seconds: u64,
+    /// File remembering what each task was last synced as.
+    pub state_path: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BackupInterval {
+    Off,
+    Hourly,
+    Daily,
+}
+
+/// Phase lengths and alerts of the Pomodoro timer.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct PomodoroConfig {
+    pub work_minutes: u32,
+    pub short_break_minutes: u32,
+    pub long_break_minutes: u32,
+    /// Take a long break after this many work phases.
+    pub long_break_every: u32,
+    /// Ring the terminal bell when a phase ends.
+    pub bell: bool,
+    /// Briefly flash the screen when a phase ends.
+    pub flash: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Created,
+    Modified,
+    Due,
+    Priority,
+    Completion,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum GroupKey {
+    Tag,
+    Due,
+}
+
 #[derive(Debug, Deserialize, Default)]
 #[serde(default)]
 pub struct ThemeConfig {
     pub colors: ColorsConfig,
     pub other: OtherConfig,
     pub icons: IconsConfig,
+    pub progress: ProgressConfig,
 }
 
 #[derive(Debug, Deserialize)]
@@ -50,6 +257,7 @@ pub struct ThemeConfig {
 pub struct ColorsConfig {
     pub main_fg: String,
     pub input_fg: String,
+    pub marked_fg: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -58,12 +266,67 @@ pub struct OtherConfig {
     pub highlight_mod: String,
 }
 
+/// How completion of a parent's subtasks is shown next to it.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ProgressConfig {
+    pub style: ProgressStyle,
+    /// Width of the inline gauge in cells.
+    pub gauge_width: usize,
+    /// Average the progress of each subtree instead of counting every descendant equally,
+    /// so a large finished branch does not hide an untouched one. Counts stay plain counts;
+    /// only the percentage and the gauge are weighted.
+    pub weighted: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressStyle {
+    Hidden,
+    /// `[3/5]`
+    Count,
+    /// `[60%]`
+    Percent,
+    /// `[3/5 60%]`
+    CountAndPercent,
+    /// `[███░░] 60%`
+    Gauge,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(default)]
 pub struct IconsConfig {
     pub expanded: String,
     pub collapsed: String,
     pub highlight_symbol: String,
+    /// Shown before tasks that are waiting on open blockers.
+    pub blocked: String,
+    /// Shown before the task whose timer is running.
+    pub timer: String,
+}
+
+/// Which destructive actions ask for confirmation before running.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ConfirmConfig {
+    /// Ask before deleting a task that still has subtasks.
+    pub delete_task: bool,
+    /// Ask before applying any action to a set of marked tasks.
+    pub bulk_actions: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// File that exported tasks are written to, as a Markdown checklist.
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct ClipboardConfig {
+    /// Also copy yanked tasks to the system clipboard as Markdown, using the OSC 52 terminal
+    /// escape sequence.
+    pub osc52: bool,
 }
 
 impl Default for IconsConfig {
@@ -72,6 +335,18 @@ impl Default for IconsConfig {
             expanded: "▼".to_string(),
             collapsed: "▶".to_string(),
             highlight_symbol: "> ".to_string(),
+            blocked: "⊘ ".to_string(),
+            timer: "⏱ ".to_string(),
+        }
+    }
+}
+
+impl Default for ProgressConfig {
+    fn default() -> Self {
+        Self {
+            style: ProgressStyle::CountAndPercent,
+            gauge_width: 10,
+            weighted: true,
         }
     }
 }
@@ -87,6 +362,43 @@ impl Default for Keybindings {
             toggle_expand: KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
             select_next: KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE),
             select_previous: KeyEvent::new(KeyCode::Char('k'), KeyModifiers::NONE),
+            toggle_mark: KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE),
+            visual_mode: KeyEvent::new(KeyCode::Char('v'), KeyModifiers::NONE),
+            clear_marks: KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+            toggle_complete: KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE),
+            move_marked: KeyEvent::new(KeyCode::Char('m'), KeyModifiers::NONE),
+            tag_task: KeyEvent::new(KeyCode::Char('t'), KeyModifiers::NONE),
+            export_tasks: KeyEvent::new(KeyCode::Char('w'), KeyModifiers::NONE),
+            yank_task: KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE),
+            cut_task: KeyEvent::new(KeyCode::Char('X'), KeyModifiers::SHIFT),
+            paste_sibling: KeyEvent::new(KeyCode::Char('p'), KeyModifiers::NONE),
+            paste_child: KeyEvent::new(KeyCode::Char('P'), KeyModifiers::SHIFT),
+            cycle_sort: KeyEvent::new(KeyCode::Char('s'), KeyModifiers::NONE),
+            sort_siblings: KeyEvent::new(KeyCode::Char('S'), KeyModifiers::SHIFT),
+            cycle_group: KeyEvent::new(KeyCode::Char('g'), KeyModifiers::NONE),
+            zoom_in: KeyEvent::new(KeyCode::Char('l'), KeyModifiers::NONE),
+            zoom_out: KeyEvent::new(KeyCode::Char('h'), KeyModifiers::NONE),
+            expand_subtree: KeyEvent::new(KeyCode::Char('>'), KeyModifiers::NONE),
+            collapse_subtree: KeyEvent::new(KeyCode::Char('<'), KeyModifiers::NONE),
+            expand_all: KeyEvent::new(KeyCode::Char('+'), KeyModifiers::NONE),
+            collapse_all: KeyEvent::new(KeyCode::Char('-'), KeyModifiers::NONE),
+            expand_to_depth: KeyEvent::new(KeyCode::Char('='), KeyModifiers::NONE),
+            toggle_details: KeyEvent::new(KeyCode::Char('i'), KeyModifiers::NONE),
+            block_task: KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE),
+            unblock_task: KeyEvent::new(KeyCode::Char('B'), KeyModifiers::SHIFT),
+            next_actionable: KeyEvent::new(KeyCode::Char('n'), KeyModifiers::NONE),
+            toggle_timer: KeyEvent::new(KeyCode::Char('T'), KeyModifiers::SHIFT),
+            toggle_pomodoro: KeyEvent::new(KeyCode::Char('o'), KeyModifiers::NONE),
+            archive_tasks: KeyEvent::new(KeyCode::Char('z'), KeyModifiers::NONE),
+            archive_completed: KeyEvent::new(KeyCode::Char('Z'), KeyModifiers::SHIFT),
+            show_archive: KeyEvent::new(KeyCode::Char('A'), KeyModifiers::SHIFT),
+            restore_task: KeyEvent::new(KeyCode::Char('u'), KeyModifiers::NONE),
+            show_trash: KeyEvent::new(KeyCode::Char('D'), KeyModifiers::SHIFT),
+            show_backups: KeyEvent::new(KeyCode::Char('R'), KeyModifiers::SHIFT),
+            show_history: KeyEvent::new(KeyCode::Char('H'), KeyModifiers::SHIFT),
+            keep_ours: KeyEvent::new(KeyCode::Char('<'), KeyModifiers::NONE),
+            keep_theirs: KeyEvent::new(KeyCode::Char('>'), KeyModifiers::NONE),
+            sync_calendar: KeyEvent::new(KeyCode::Char('C'), KeyModifiers::SHIFT),
         }
     }
 }
@@ -105,6 +417,7 @@ impl Default for ColorsConfig {
         Self {
             main_fg: "White".to_string(),
             input_fg: "Yellow".to_string(),
+            marked_fg: "Cyan".to_string(),
         }
     }
 }
@@ -117,6 +430,125 @@ impl Default for OtherConfig {
     }
 }
 
+impl Default for ViewConfig {
+    fn default() -> Self {
+        Self {
+            sort: None,
+            group: None,
+            remember_expanded: true,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            path: "tasks.json".to_string(),
+            snapshot_every: 100,
+            sync_seconds: 2,
+        }
+    }
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            path: "archive.json".to_string(),
+            older_than_days: 7,
+        }
+    }
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            path: "trash.json".to_string(),
+            retention_days: 30,
+        }
+    }
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: "backups".to_string(),
+            interval: BackupInterval::Hourly,
+            keep: 24,
+        }
+    }
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            batch_seconds: 5,
+        }
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 7979,
+            token: String::new(),
+        }
+    }
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            socket: "vinegar.sock".to_string(),
+        }
+    }
+}
+
+impl Default for CalDavConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            username: String::new(),
+            password: String::new(),
+            interval_seconds: 300,
+            state_path: "caldav-state.json".to_string(),
+        }
+    }
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            work_minutes: 25,
+            short_break_minutes: 5,
+            long_break_minutes: 15,
+            long_break_every: 4,
+            bell: true,
+            flash: true,
+        }
+    }
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        Self {
+            delete_task: true,
+            bulk_actions: true,
+        }
+    }
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            path: "vinegar-export.md".to_string(),
+        }
+    }
+}
+
 pub fn load_config() -> Config {
     let path = Path::new("config.toml");
     if path.exists() {
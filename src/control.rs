@@ -0,0 +1,244 @@
+use crate::app::task::TaskId;
+use crate::config::Config;
+use crate::event::{AppEvent, Event};
+use crate::server::SharedTasks;
+use color_eyre::eyre::{bail, eyre, WrapErr};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader as StdBufReader, Write};
+use std::os::unix::net::UnixStream as StdUnixStream;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::UnboundedSender;
+use uuid::Uuid;
+
+/// A command read from the control socket, one JSON object per line, such as
+/// `{"cmd": "add", "input": "Buy milk #home"}`. Each mirrors an [`AppEvent`].
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Command {
+    Add {
+        input: String,
+        #[serde(default)]
+        parent: Option<TaskId>,
+    },
+    Edit {
+        id: TaskId,
+        input: String,
+    },
+    Delete {
+        ids: Vec<TaskId>,
+    },
+    Toggle {
+        ids: Vec<TaskId>,
+        #[serde(default)]
+        force: bool,
+    },
+    Move {
+        ids: Vec<TaskId>,
+        #[serde(default)]
+        parent: Option<TaskId>,
+    },
+    Tag {
+        ids: Vec<TaskId>,
+        tag: String,
+    },
+    Select {
+        id: TaskId,
+    },
+    /// Reply with the whole tree.
+    Get,
+    /// Stream notifications on this connection from now on.
+    Subscribe,
+    Quit,
+}
+
+/// A change in the running instance, sent to subscribed connections as one JSON line such as
+/// `{"event": "selection_changed", "id": "…", "name": "Buy milk"}`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    TasksChanged {
+        open: usize,
+        total: usize,
+    },
+    SelectionChanged {
+        id: Option<TaskId>,
+        name: Option<String>,
+    },
+}
+
+/// Listens on the Unix socket at `path`. Commands become events for the app, replies and
+/// notifications are written back on the same connection. A socket file left behind by an
+/// instance that is gone is replaced; one still answering is an error.
+pub fn start(
+    path: &Path,
+    sender: UnboundedSender<Event>,
+    tasks: SharedTasks,
+    notifications: broadcast::Sender<Notification>,
+) -> color_eyre::Result<()> {
+    if path.exists() {
+        if StdUnixStream::connect(path).is_ok() {
+            bail!("{} is in use by another instance", path.display());
+        }
+        fs::remove_file(path).wrap_err_with(|| format!("Failed to remove {}", path.display()))?;
+    }
+    let listener =
+        UnixListener::bind(path).wrap_err_with(|| format!("Failed to bind {}", path.display()))?;
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let client = (sender.clone(), tasks.clone(), notifications.clone());
+            tokio::spawn(async move { handle_client(stream, client.0, client.1, client.2).await });
+        }
+    });
+    Ok(())
+}
+
+async fn handle_client(
+    stream: UnixStream,
+    sender: UnboundedSender<Event>,
+    tasks: SharedTasks,
+    notifications: broadcast::Sender<Notification>,
+) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut subscription = None;
+    loop {
+        let output = tokio::select! {
+            line = lines.next_line() => {
+                let Ok(Some(line)) = line else {
+                    break;
+                };
+                match serde_json::from_str::<Command>(&line) {
+                    Ok(Command::Subscribe) => {
+                        subscription = Some(notifications.subscribe());
+                        json!({ "ok": true })
+                    }
+                    Ok(command) => run_command(command, &sender, &tasks),
+                    Err(err) => json!({ "ok": false, "error": err.to_string() }),
+                }
+            }
+            Some(notification) = next_notification(&mut subscription) => json!(notification),
+        };
+        if writer
+            .write_all(format!("{}\n", output).as_bytes())
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+/// Waits for the next notification, or forever if the connection has not subscribed.
+async fn next_notification(
+    subscription: &mut Option<broadcast::Receiver<Notification>>,
+) -> Option<Notification> {
+    let Some(receiver) = subscription else {
+        return std::future::pending().await;
+    };
+    loop {
+        match receiver.recv().await {
+            Ok(notification) => return Some(notification),
+            // A slow reader misses some notifications rather than holding the others up
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+fn run_command(command: Command, sender: &UnboundedSender<Event>, tasks: &SharedTasks) -> Value {
+    let event = match command {
+        Command::Add { input, parent } => AppEvent::AddTask {
+            id: Uuid::new_v4(),
+            parent,
+            input,
+        },
+        Command::Edit { id, input } => AppEvent::UpdateTask { id, input },
+        Command::Delete { ids } => AppEvent::DeleteTasks { ids },
+        Command::Toggle { ids, force } => AppEvent::ToggleComplete { ids, force },
+        Command::Move { ids, parent } => AppEvent::MoveTasks { ids, parent },
+        Command::Tag { ids, tag } => AppEvent::TagTasks { ids, tag },
+        Command::Select { id } => AppEvent::SelectTask { id },
+        Command::Quit => AppEvent::Quit,
+        Command::Get => {
+            return match tasks.read() {
+                Ok(tasks) => json!({ "ok": true, "tasks": *tasks }),
+                Err(_) => json!({ "ok": false, "error": "Task tree unavailable" }),
+            };
+        }
+        Command::Subscribe => return json!({ "ok": true }),
+    };
+    match sender.send(Event::App(event)) {
+        Ok(()) => json!({ "ok": true }),
+        Err(_) => json!({ "ok": false, "error": "vinegar is shutting down" }),
+    }
+}
+
+/// The `remote` command: sends one command to the running instance and prints its reply.
+/// `subscribe` keeps printing notifications until the instance quits.
+///
+/// ```text
+/// vinegar remote add <input> [parent-id]
+/// vinegar remote edit <id> <input>
+/// vinegar remote toggle|delete <id>...
+/// vinegar remote move <id> [parent-id]
+/// vinegar remote tag <tag> <id>...
+/// vinegar remote select <id>
+/// vinegar remote get|subscribe|quit
+/// vinegar remote '{"cmd": "add", "input": "Buy milk"}'
+/// ```
+///
+/// Returns `false` if the instance rejected the command.
+pub fn remote_command(config: &Config, args: &[String]) -> color_eyre::Result<bool> {
+    let command = build_command(args)?;
+    let path = Path::new(&config.control.socket);
+    let mut stream = StdUnixStream::connect(path).wrap_err_with(|| {
+        format!(
+            "Could not connect to {}; is vinegar running with [control] enabled?",
+            path.display()
+        )
+    })?;
+    writeln!(stream, "{}", command)?;
+    let subscribe = command["cmd"] == "subscribe";
+    for line in StdBufReader::new(stream).lines() {
+        let line = line?;
+        println!("{}", line);
+        if !subscribe {
+            let reply: Value = serde_json::from_str(&line)?;
+            return Ok(reply["ok"] == true);
+        }
+    }
+    Ok(true)
+}
+
+fn build_command(args: &[String]) -> color_eyre::Result<Value> {
+    let usage = || {
+        eyre!("Usage: vinegar remote <add|edit|toggle|delete|move|tag|select|get|subscribe|quit> [args]")
+    };
+    let (name, rest) = args.split_first().ok_or_else(usage)?;
+    if name.starts_with('{') {
+        return serde_json::from_str(name).wrap_err("Invalid JSON command");
+    }
+    let command = match (name.as_str(), rest) {
+        ("add", [input]) => json!({ "cmd": "add", "input": input }),
+        ("add", [input, parent]) => json!({ "cmd": "add", "input": input, "parent": parent }),
+        ("edit", [id, input]) => json!({ "cmd": "edit", "id": id, "input": input }),
+        ("toggle" | "delete", ids) if !ids.is_empty() => json!({ "cmd": name, "ids": ids }),
+        ("move", [id]) => json!({ "cmd": "move", "ids": [id] }),
+        ("move", [id, parent]) => json!({ "cmd": "move", "ids": [id], "parent": parent }),
+        ("tag", [tag, ids @ ..]) if !ids.is_empty() => {
+            json!({ "cmd": "tag", "tag": tag, "ids": ids })
+        }
+        ("select", [id]) => json!({ "cmd": "select", "id": id }),
+        ("get" | "subscribe" | "quit", []) => json!({ "cmd": name }),
+        _ => return Err(usage()),
+    };
+    Ok(command)
+}
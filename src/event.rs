@@ -1,11 +1,15 @@
+use crate::app::merge::Side;
+use crate::app::task::TaskId;
+use crate::caldav::SyncOutcome;
 use color_eyre::eyre::OptionExt;
 use futures::{FutureExt, StreamExt};
 use ratatui::crossterm::event::Event as CrosstermEvent;
+use std::path::PathBuf;
 use std::time::Duration;
 use tokio::sync::mpsc;
 
 /// The frequency at which tick events are emitted.
-const TICK_FPS: f64 = 30.0;
+pub const TICK_FPS: f64 = 30.0;
 
 /// Representation of all possible events.
 #[derive(Clone, Debug)]
@@ -29,14 +33,77 @@ pub enum Event {
 /// Application events.
 ///
 /// You can extend this enum with your own custom events.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum AppEvent {
     /// Quit the application.
     Quit,
-    // Add new task
-    AddTask,
-    DeleteTask,
-    UpdateTask,
+    /// Add a task parsed from `input` under `parent`, or at the top level. The ID is chosen
+    /// by the sender so it can tell others about the task before it exists.
+    AddTask {
+        id: TaskId,
+        parent: Option<TaskId>,
+        input: String,
+    },
+    /// Replace a task's name and attributes with those parsed from `input`.
+    UpdateTask { id: TaskId, input: String },
+    /// Move tasks together with their subtasks into the trash.
+    DeleteTasks { ids: Vec<TaskId> },
+    /// Complete the tasks, or reopen them if they are all complete. Blocked tasks are only
+    /// completed if `force` is set.
+    ToggleComplete { ids: Vec<TaskId>, force: bool },
+    /// Move tasks under `parent`, or to the top level.
+    MoveTasks {
+        ids: Vec<TaskId>,
+        parent: Option<TaskId>,
+    },
+    /// Add `tag` to the tasks.
+    TagTasks { ids: Vec<TaskId>, tag: String },
+    /// Export the tasks as Markdown.
+    ExportTasks { ids: Vec<TaskId> },
+    /// Copy the tasks into the clipboard.
+    YankTasks { ids: Vec<TaskId> },
+    /// Move the tasks into the clipboard.
+    CutTasks { ids: Vec<TaskId> },
+    /// Paste the clipboard after `target`, or inside it if `as_child` is set. A missing target
+    /// pastes at the top level.
+    PasteTasks {
+        target: Option<TaskId>,
+        as_child: bool,
+    },
+    /// Permanently reorder a task and its siblings.
+    SortSiblings { id: TaskId },
+    /// Make a task wait on `blockers`.
+    BlockTask { id: TaskId, blockers: Vec<TaskId> },
+    /// Remove every dependency of a task.
+    UnblockTask { id: TaskId },
+    /// Start timing a task, or stop its timer if it is already running.
+    ToggleTimer { id: TaskId },
+    /// Start a Pomodoro session on a task, or cancel the running one.
+    TogglePomodoro { id: TaskId },
+    /// Move the completed ones among the tasks into the archive.
+    ArchiveTasks { ids: Vec<TaskId> },
+    /// Archive every task completed at least `older_than_days` days ago.
+    ArchiveCompleted { older_than_days: u32 },
+    /// Put an archived task back into the tree.
+    RestoreArchived { id: TaskId },
+    /// Put a deleted task back into the tree.
+    RestoreTrashed { id: TaskId },
+    /// Permanently remove entries from the trash.
+    PurgeTrashed { ids: Vec<TaskId> },
+    /// Apply the changes recovered from the journal of a session that crashed.
+    ReplayJournal,
+    /// Replace the task tree with the one saved in a backup file.
+    RestoreBackup { path: PathBuf },
+    /// Replace the task tree with the one committed in `hash` and commit the result.
+    RollBack { hash: String },
+    /// Select the task, or its nearest visible ancestor.
+    SelectTask { id: TaskId },
+    /// Settle the merge conflict on a task with one side's version.
+    ResolveConflict { id: TaskId, side: Side },
+    /// A background calendar sync finished, with the changes it asks of the tree.
+    CalendarSynced {
+        result: Result<Box<SyncOutcome>, String>,
+    },
 }
 
 /// Terminal event handler.
@@ -73,6 +140,11 @@ impl EventHandler {
             .ok_or_eyre("Failed to receive event")
     }
 
+    /// A sender other tasks can use to feed events into the app's event loop.
+    pub fn sender(&self) -> mpsc::UnboundedSender<Event> {
+        self.sender.clone()
+    }
+
     /// Queue an app event to be sent to the event receiver.
     ///
     /// This is useful for sending events to the event handler which will be processed by the next
@@ -0,0 +1,166 @@
+use crate::app::task::Task;
+use crate::config::Config;
+use chrono::{DateTime, Local, TimeZone};
+use color_eyre::eyre::{bail, WrapErr};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A commit that touched the data file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub hash: String,
+    pub time: DateTime<Local>,
+    pub subject: String,
+}
+
+impl Commit {
+    pub fn short_hash(&self) -> &str {
+        &self.hash[..self.hash.len().min(7)]
+    }
+}
+
+/// Name of the repository vinegar keeps inside the data file's directory. It is separate
+/// from any repository around that directory, so commits never land on a project's branch.
+const GIT_DIR: &str = ".vinegar-git";
+
+/// The local git repository the data file is committed to, driven through the `git`
+/// command so no network access or extra library is needed.
+#[derive(Debug)]
+pub struct GitStore {
+    /// Directory holding the data file and, in [`GIT_DIR`], its repository.
+    dir: PathBuf,
+    /// Name of the data file inside `dir`.
+    file: String,
+}
+
+impl GitStore {
+    /// Opens vinegar's repository next to the data file, initialising it if there is none.
+    pub fn open(config: &Config) -> color_eyre::Result<Self> {
+        let path = Path::new(&config.storage.path);
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let Some(file) = path.file_name().and_then(|name| name.to_str()) else {
+            bail!("{} is not a file path", path.display());
+        };
+        let store = Self {
+            dir,
+            file: file.to_string(),
+        };
+        if !store.dir.join(GIT_DIR).exists() {
+            store.git(&["init", "--quiet"])?;
+            // Let plain `git --git-dir=.vinegar-git` find the work tree, and keep the other
+            // files in the directory out of its status
+            store.git(&["config", "core.worktree", ".."])?;
+            store.git(&["config", "status.showUntrackedFiles", "no"])?;
+        }
+        Ok(store)
+    }
+
+    /// Whether a merge is in progress, waiting for the user to finish it.
+    pub fn merging(&self) -> bool {
+        self.git(&["rev-parse", "--quiet", "--verify", "MERGE_HEAD"])
+            .is_ok()
+    }
+
+    /// Commits the data file as it is on disk. Does nothing if it has not changed, or while a
+    /// merge is in progress: staging the file would drop the conflict stages the merge
+    /// screen reads, so staging and the merge commit are left to the user.
+    pub fn commit(&self, message: &str) -> color_eyre::Result<()> {
+        if self.merging() {
+            return Ok(());
+        }
+        // Versioning was asked for, so commit the file even if an ignore rule matches it
+        self.git(&["add", "--force", "--", &self.file])?;
+        if self
+            .git(&["diff", "--cached", "--quiet", "--", &self.file])
+            .is_ok()
+        {
+            return Ok(());
+        }
+        let mut args = Vec::new();
+        // Fall back to a local identity so commits work on machines without git set up
+        if self.git(&["config", "user.name"]).is_err() {
+            args.extend(["-c", "user.name=vinegar"]);
+        }
+        if self.git(&["config", "user.email"]).is_err() {
+            args.extend(["-c", "user.email=vinegar@localhost"]);
+        }
+        args.extend(["commit", "--quiet", "-m", message, "--", &self.file]);
+        self.git(&args)?;
+        Ok(())
+    }
+
+    /// The most recent commits of the data file, newest first.
+    pub fn history(&self, limit: usize) -> color_eyre::Result<Vec<Commit>> {
+        let limit = format!("--max-count={}", limit);
+        let output = match self.git(&["log", &limit, "--format=%H%x1f%ct%x1f%s", "--", &self.file])
+        {
+            Ok(output) => output,
+            // A repository without commits has no history yet
+            Err(_) => return Ok(Vec::new()),
+        };
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\x1f');
+                let hash = fields.next()?.to_string();
+                let time = Local
+                    .timestamp_opt(fields.next()?.parse().ok()?, 0)
+                    .single()?;
+                let subject = fields.next().unwrap_or_default().to_string();
+                Some(Commit {
+                    hash,
+                    time,
+                    subject,
+                })
+            })
+            .collect())
+    }
+
+    /// The task tree as it was committed in `hash`.
+    pub fn tasks_at(&self, hash: &str) -> color_eyre::Result<Vec<Task>> {
+        let data = self.git(&["show", &format!("{}:./{}", hash, self.file)])?;
+        serde_json::from_str(&data)
+            .wrap_err_with(|| format!("Failed to parse {} at {}", self.file, hash))
+    }
+
+    /// The common ancestor, current and incoming versions of the data file while a merge of
+    /// it is unresolved, or `None` if it is not in conflict.
+    pub fn unmerged_versions(&self) -> color_eyre::Result<Option<[Vec<Task>; 3]>> {
+        let unmerged = self.git(&["ls-files", "--unmerged", "--", &self.file])?;
+        if unmerged.trim().is_empty() {
+            return Ok(None);
+        }
+        let stage = |number: u8| -> color_eyre::Result<Vec<Task>> {
+            // A side that has no version of the file counts as an empty tree
+            match self.git(&["show", &format!(":{}:./{}", number, self.file)]) {
+                Ok(data) if !data.trim().is_empty() => serde_json::from_str(&data)
+                    .wrap_err_with(|| format!("Failed to parse {} stage {}", self.file, number)),
+                _ => Ok(Vec::new()),
+            }
+        };
+        Ok(Some([stage(1)?, stage(2)?, stage(3)?]))
+    }
+
+    /// Runs git on vinegar's repository and returns its output.
+    fn git(&self, args: &[&str]) -> color_eyre::Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .arg(format!("--git-dir={}", GIT_DIR))
+            .arg("--work-tree=.")
+            .args(args)
+            .output()
+            .wrap_err("Failed to run git")?;
+        if !output.status.success() {
+            bail!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
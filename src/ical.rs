@@ -0,0 +1,538 @@
+use crate::app::flat::{self, FlatTask, FlatTree};
+use crate::app::task::{Priority, Task, TaskId};
+use crate::config::Config;
+use crate::storage;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+use color_eyre::eyre::{eyre, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use uuid::Uuid;
+
+const DATE_FORMAT: &str = "%Y%m%d";
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+/// Longest content line allowed before it must be folded, in bytes.
+const MAX_LINE: usize = 75;
+
+/// Parameters of a content line as upper-cased names and their values.
+type Params = Vec<(String, String)>;
+
+/// A task as an iCalendar VTODO, holding the properties tasks are mapped onto.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Todo {
+    pub uid: String,
+    pub summary: String,
+    pub completed: bool,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub due: Option<NaiveDate>,
+    pub priority: Option<Priority>,
+    pub categories: Vec<String>,
+    /// UID of the parent to-do, from `RELATED-TO;RELTYPE=PARENT`.
+    pub parent: Option<String>,
+    pub created: Option<DateTime<Utc>>,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl Todo {
+    /// The VTODO for `task`, which is identified by `uid` and sits under the to-do `parent`.
+    pub fn from_task(task: &Task, uid: String, parent: Option<String>) -> Self {
+        Self {
+            uid,
+            summary: task.name.clone(),
+            completed: task.completed,
+            completed_at: task.completed_at.map(|at| at.with_timezone(&Utc)),
+            due: task.due,
+            priority: task.priority,
+            categories: task.tags.clone(),
+            parent,
+            created: Some(task.created.with_timezone(&Utc)),
+            last_modified: Some(task.modified.with_timezone(&Utc)),
+        }
+    }
+
+    /// A new task from this to-do, without subtasks or a place in the tree.
+    pub fn to_task(&self, id: TaskId) -> Task {
+        let mut task = Task::new(self.summary.clone());
+        task.id = id;
+        if let Some(created) = self.created {
+            task.created = created.with_timezone(&Local);
+        }
+        self.apply_to(&mut task);
+        task
+    }
+
+    /// Copies the mapped properties onto an existing task.
+    pub fn apply_to(&self, task: &mut Task) {
+        task.name = self.summary.clone();
+        task.due = self.due;
+        task.priority = self.priority;
+        task.tags = self.categories.clone();
+        if task.completed != self.completed {
+            task.set_completed(self.completed);
+        }
+        if let Some(completed_at) = self.completed_at.filter(|_| self.completed) {
+            task.completed_at = Some(completed_at.with_timezone(&Local));
+        }
+        if let Some(last_modified) = self.last_modified {
+            task.modified = last_modified.with_timezone(&Local);
+        }
+    }
+
+    /// Whether both describe the same task, ignoring timestamps that only record when
+    /// something happened.
+    pub fn same_content(&self, other: &Todo) -> bool {
+        self.summary == other.summary
+            && self.completed == other.completed
+            && self.due == other.due
+            && self.priority == other.priority
+            && self.categories == other.categories
+            && self.parent == other.parent
+    }
+
+    fn write(&self, out: &mut String) {
+        write_line(out, "BEGIN:VTODO");
+        write_line(out, &format!("UID:{}", escape(&self.uid)));
+        write_line(
+            out,
+            &format!("DTSTAMP:{}", Utc::now().format(DATE_TIME_FORMAT)),
+        );
+        write_line(out, &format!("SUMMARY:{}", escape(&self.summary)));
+        let status = if self.completed {
+            "COMPLETED"
+        } else {
+            "NEEDS-ACTION"
+        };
+        write_line(out, &format!("STATUS:{}", status));
+        if let Some(completed_at) = self.completed_at.filter(|_| self.completed) {
+            write_line(
+                out,
+                &format!("COMPLETED:{}", completed_at.format(DATE_TIME_FORMAT)),
+            );
+        }
+        if let Some(due) = self.due {
+            write_line(out, &format!("DUE;VALUE=DATE:{}", due.format(DATE_FORMAT)));
+        }
+        if let Some(priority) = self.priority {
+            let value = match priority {
+                Priority::High => 1,
+                Priority::Medium => 5,
+                Priority::Low => 9,
+            };
+            write_line(out, &format!("PRIORITY:{}", value));
+        }
+        if !self.categories.is_empty() {
+            let categories: Vec<String> = self.categories.iter().map(|tag| escape(tag)).collect();
+            write_line(out, &format!("CATEGORIES:{}", categories.join(",")));
+        }
+        if let Some(parent) = &self.parent {
+            write_line(
+                out,
+                &format!("RELATED-TO;RELTYPE=PARENT:{}", escape(parent)),
+            );
+        }
+        if let Some(created) = self.created {
+            write_line(
+                out,
+                &format!("CREATED:{}", created.format(DATE_TIME_FORMAT)),
+            );
+        }
+        if let Some(last_modified) = self.last_modified {
+            write_line(
+                out,
+                &format!("LAST-MODIFIED:{}", last_modified.format(DATE_TIME_FORMAT)),
+            );
+        }
+        write_line(out, "END:VTODO");
+    }
+}
+
+/// A VCALENDAR object holding the to-dos.
+pub fn write_calendar(todos: &[Todo]) -> String {
+    let mut out = String::new();
+    write_line(&mut out, "BEGIN:VCALENDAR");
+    write_line(&mut out, "VERSION:2.0");
+    write_line(&mut out, "PRODID:-//vinegar//vinegar//EN");
+    for todo in todos {
+        todo.write(&mut out);
+    }
+    write_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// Every VTODO in an iCalendar text. Other components and unknown properties are skipped,
+/// and so are to-dos without a UID.
+pub fn parse_calendar(text: &str) -> Vec<Todo> {
+    let mut todos = Vec::new();
+    let mut current: Option<Todo> = None;
+    for line in unfold(text) {
+        let Some((name, params, value)) = split_property(&line) else {
+            continue;
+        };
+        match name.as_str() {
+            "BEGIN" if value.eq_ignore_ascii_case("VTODO") => {
+                current = Some(Todo {
+                    uid: String::new(),
+                    summary: String::new(),
+                    completed: false,
+                    completed_at: None,
+                    due: None,
+                    priority: None,
+                    categories: Vec::new(),
+                    parent: None,
+                    created: None,
+                    last_modified: None,
+                });
+            }
+            "END" if value.eq_ignore_ascii_case("VTODO") => {
+                if let Some(todo) = current.take().filter(|todo| !todo.uid.is_empty()) {
+                    todos.push(todo);
+                }
+            }
+            _ => {
+                if let Some(todo) = &mut current {
+                    parse_property(todo, &name, &params, value);
+                }
+            }
+        }
+    }
+    todos
+}
+
+/// The tree as a calendar of VTODOs, each subtask linked to its parent with `RELATED-TO`.
+/// Task IDs become the UIDs, so exporting and importing again keeps them.
+pub fn tasks_to_calendar(tasks: &[Task]) -> String {
+    let mut todos = Vec::new();
+    let mut stack: Vec<(&Task, Option<TaskId>)> =
+        tasks.iter().rev().map(|task| (task, None)).collect();
+    while let Some((task, parent)) = stack.pop() {
+        let parent = parent.map(|id| id.to_string());
+        todos.push(Todo::from_task(task, task.id.to_string(), parent));
+        stack.extend(
+            task.subtasks
+                .iter()
+                .rev()
+                .map(|subtask| (subtask, Some(task.id))),
+        );
+    }
+    write_calendar(&todos)
+}
+
+/// The to-dos in a calendar as a tree, nested by their `RELATED-TO` parents in the order they
+/// appear. UIDs that are task IDs are kept; others get a fresh ID.
+pub fn tasks_from_calendar(text: &str) -> Vec<Task> {
+    let todos = parse_calendar(text);
+    let ids: HashMap<&str, TaskId> = todos
+        .iter()
+        .map(|todo| {
+            let id = Uuid::parse_str(&todo.uid).unwrap_or_else(|_| Uuid::new_v4());
+            (todo.uid.as_str(), id)
+        })
+        .collect();
+    let tree: FlatTree = todos
+        .iter()
+        .enumerate()
+        .map(|(position, todo)| {
+            let id = ids[todo.uid.as_str()];
+            let entry = FlatTask {
+                task: todo.to_task(id),
+                parent: todo.parent.as_deref().and_then(|uid| ids.get(uid).copied()),
+                position,
+            };
+            (id, entry)
+        })
+        .collect();
+    flat::unflatten(&tree)
+}
+
+/// The `export-ics` command: writes the whole tree to an `.ics` file calendar apps can open.
+pub fn export_command(config: &Config, path: Option<&str>) -> color_eyre::Result<()> {
+    let path = path.ok_or_else(|| eyre!("Usage: vinegar export-ics <file.ics>"))?;
+    let tasks = storage::load_tasks(config)?;
+    fs::write(path, tasks_to_calendar(&tasks))
+        .wrap_err_with(|| format!("Failed to write {}", path))?;
+    let count: usize = tasks.iter().map(|task| 1 + task.descendant_count()).sum();
+    println!("Exported {} task(s) to {}", count, path);
+    Ok(())
+}
+
+/// The `import-ics` command: adds the to-dos in an `.ics` file to the end of the tree. Tasks
+/// already in the tree, such as those from an earlier import of the same file, are skipped.
+/// A running instance picks the result up like any other change to the data file.
+pub fn import_command(config: &Config, path: Option<&str>) -> color_eyre::Result<()> {
+    let path = path.ok_or_else(|| eyre!("Usage: vinegar import-ics <file.ics>"))?;
+    let text = fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path))?;
+    let mut tree = flat::flatten(&storage::load_tasks(config)?);
+    let imported = flat::flatten(&tasks_from_calendar(&text));
+    let total = imported.len();
+    let added = add_new(&mut tree, imported);
+    storage::save_tasks(config, &flat::unflatten(&tree))?;
+    println!(
+        "Imported {} task(s) from {} into {}",
+        added, path, config.storage.path
+    );
+    if added < total {
+        println!("Skipped {} task(s) already there", total - added);
+    }
+    Ok(())
+}
+
+/// Adds the imported tasks that are not in `tree` yet after those already there, and
+/// returns how many were added. New subtasks of tasks already in the tree go under them.
+fn add_new(tree: &mut FlatTree, imported: FlatTree) -> usize {
+    let offset = tree.len();
+    let mut added = 0;
+    for (id, mut entry) in imported {
+        if tree.contains_key(&id) {
+            continue;
+        }
+        entry.position += offset;
+        tree.insert(id, entry);
+        added += 1;
+    }
+    added
+}
+
+fn parse_property(todo: &mut Todo, name: &str, params: &[(String, String)], value: &str) {
+    match name {
+        "UID" => todo.uid = unescape(value),
+        "SUMMARY" => todo.summary = unescape(value),
+        "STATUS" => todo.completed = value.eq_ignore_ascii_case("COMPLETED"),
+        "COMPLETED" => todo.completed_at = parse_date_time(value, params),
+        "DUE" if value.contains('T') => {
+            todo.due =
+                parse_date_time(value, params).map(|due| due.with_timezone(&Local).date_naive())
+        }
+        "DUE" => todo.due = parse_date(value),
+        "PRIORITY" => {
+            todo.priority = match value.trim().parse::<u8>() {
+                Ok(1..=4) => Some(Priority::High),
+                Ok(5) => Some(Priority::Medium),
+                Ok(6..=9) => Some(Priority::Low),
+                _ => None,
+            }
+        }
+        "CATEGORIES" => todo.categories.extend(
+            split_unescaped(value, ',')
+                .into_iter()
+                .map(|category| unescape(&category))
+                .filter(|category| !category.is_empty()),
+        ),
+        "RELATED-TO" => {
+            let is_parent = params
+                .iter()
+                .find(|(param, _)| param == "RELTYPE")
+                .is_none_or(|(_, reltype)| reltype.eq_ignore_ascii_case("PARENT"));
+            if is_parent {
+                todo.parent = Some(unescape(value));
+            }
+        }
+        "CREATED" => todo.created = parse_date_time(value, params),
+        "LAST-MODIFIED" => todo.last_modified = parse_date_time(value, params),
+        _ => {}
+    }
+}
+
+/// Joins folded lines back together.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Splits a content line into its upper-cased name, parameters and value.
+fn split_property(line: &str) -> Option<(String, Params, &str)> {
+    // The value starts at the first colon outside a quoted parameter value
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(index, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(index),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    let params = parts
+        .filter_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            Some((
+                key.to_ascii_uppercase(),
+                value.trim_matches('"').to_string(),
+            ))
+        })
+        .collect();
+    Some((name, params, value))
+}
+
+/// A `DATE`.
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..8)?, DATE_FORMAT).ok()
+}
+
+/// A `DATE-TIME`. Times ending in `Z` or zoned to UTC are UTC. Floating times, and those
+/// with any other `TZID`, are read as local time, as no time zone database is bundled to
+/// look the zone up; calendar apps mostly name the zone their user is in.
+fn parse_date_time(value: &str, params: &[(String, String)]) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    let zoned_utc = params.iter().any(|(param, zone)| {
+        param == "TZID"
+            && matches!(
+                zone.to_ascii_uppercase().as_str(),
+                "UTC" | "ETC/UTC" | "GMT"
+            )
+    });
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|at| at.and_utc());
+    }
+    let at = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    if zoned_utc {
+        return Some(at.and_utc());
+    }
+    at.and_local_timezone(Local)
+        .earliest()
+        .map(|at| at.with_timezone(&Utc))
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Splits on `separator` where it is not escaped, keeping the escapes in the parts.
+fn split_unescaped(text: &str, separator: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let last = parts.last_mut().expect("parts is never empty");
+                last.push(c);
+                last.extend(chars.next());
+            }
+            c if c == separator => parts.push(String::new()),
+            c => parts.last_mut().expect("parts is never empty").push(c),
+        }
+    }
+    parts
+}
+
+/// Writes a content line, folding it so no line is longer than [`MAX_LINE`] bytes.
+fn write_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn calendar(lines: &[&str]) -> String {
+        let mut text = String::from("BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:a\r\n");
+        for line in lines {
+            text.push_str(line);
+            text.push_str("\r\n");
+        }
+        text.push_str("END:VTODO\r\nEND:VCALENDAR\r\n");
+        text
+    }
+
+    #[test]
+    fn reads_utc_floating_and_zoned_times() {
+        let local = Local
+            .with_ymd_and_hms(2024, 5, 10, 9, 30, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let utc = Utc.with_ymd_and_hms(2024, 5, 10, 9, 30, 0).unwrap();
+        for (line, expected) in [
+            ("COMPLETED:20240510T093000Z", utc),
+            ("COMPLETED;TZID=UTC:20240510T093000", utc),
+            ("COMPLETED:20240510T093000", local),
+            ("COMPLETED;TZID=Europe/Berlin:20240510T093000", local),
+        ] {
+            let todo = &parse_calendar(&calendar(&[line]))[0];
+            assert_eq!(todo.completed_at, Some(expected), "{}", line);
+        }
+    }
+
+    #[test]
+    fn reads_the_local_date_of_a_due_time() {
+        let due = Utc.with_ymd_and_hms(2024, 5, 10, 23, 30, 0).unwrap();
+        let todo = &parse_calendar(&calendar(&["DUE:20240510T233000Z"]))[0];
+        assert_eq!(todo.due, Some(due.with_timezone(&Local).date_naive()));
+        let todo = &parse_calendar(&calendar(&["DUE;VALUE=DATE:20240510"]))[0];
+        assert_eq!(todo.due, NaiveDate::from_ymd_opt(2024, 5, 10));
+    }
+
+    #[test]
+    fn imports_new_subtasks_of_existing_tasks_under_them() {
+        let mut parent = Task::new("Plan trip".to_string());
+        parent.subtasks.push(Task::new("Book flights".to_string()));
+        let existing = vec![parent.clone(), Task::new("Water plants".to_string())];
+        let mut exported = existing.clone();
+        exported[0]
+            .subtasks
+            .push(Task::new("Book hotel".to_string()));
+        exported.push(Task::new("Call the bank".to_string()));
+        let imported = tasks_from_calendar(&tasks_to_calendar(&exported));
+
+        let mut tree = flat::flatten(&existing);
+        let added = add_new(&mut tree, flat::flatten(&imported));
+        let tasks = flat::unflatten(&tree);
+
+        assert_eq!(added, 2);
+        let names: Vec<&str> = tasks.iter().map(|task| task.name.as_str()).collect();
+        assert_eq!(names, ["Plan trip", "Water plants", "Call the bank"]);
+        let subtasks: Vec<&str> = tasks[0]
+            .subtasks
+            .iter()
+            .map(|task| task.name.as_str())
+            .collect();
+        assert_eq!(subtasks, ["Book flights", "Book hotel"]);
+    }
+}
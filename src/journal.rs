@@ -0,0 +1,256 @@
+use crate::app::flat::FlatOp;
+use crate::config::Config;
+use chrono::{DateTime, Local};
+use color_eyre::eyre::WrapErr;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Changes made by one event, as written to the journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub at: DateTime<Local>,
+    pub ops: Vec<FlatOp>,
+}
+
+/// Append-only log of the changes made to the task tree since the last snapshot, one JSON
+/// entry per line. Every entry is synced to disk before the next event is handled, so a
+/// killed process loses at most the change it was in the middle of. Each instance writes its
+/// own journal and holds a lock on it while running; a clean shutdown removes the file, so
+/// an unlocked journal is how a crash is detected on the next start.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: Option<File>,
+    entries: usize,
+}
+
+impl Journal {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            path: PathBuf::from(format!(
+                "{}{}",
+                journal_prefix(config).display(),
+                Uuid::new_v4()
+            )),
+            file: None,
+            entries: 0,
+        }
+    }
+
+    /// Appends an entry and waits until it is on disk.
+    pub fn append(&mut self, ops: Vec<FlatOp>) -> color_eyre::Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(
+                open_locked(&self.path)
+                    .wrap_err_with(|| format!("Failed to open {}", self.path.display()))?,
+            ),
+        };
+        let entry = JournalEntry {
+            at: Local::now(),
+            ops,
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())
+            .and_then(|()| file.sync_data())
+            .wrap_err_with(|| format!("Failed to write {}", self.path.display()))?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Number of entries written since the last snapshot.
+    pub fn entries_since_snapshot(&self) -> usize {
+        self.entries
+    }
+
+    /// Empties the journal once a snapshot has made its entries redundant. The file is kept,
+    /// and with it the lock, so no other instance mistakes it for a crashed one's.
+    pub fn clear(&mut self) -> color_eyre::Result<()> {
+        self.entries = 0;
+        if let Some(file) = &self.file {
+            file.set_len(0)
+                .and_then(|()| file.sync_data())
+                .wrap_err_with(|| format!("Failed to empty {}", self.path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Removes the journal on a clean shutdown, after the final snapshot.
+    pub fn close(&mut self) -> color_eyre::Result<()> {
+        self.entries = 0;
+        if self.file.take().is_some() {
+            fs::remove_file(&self.path)
+                .wrap_err_with(|| format!("Failed to remove {}", self.path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Opens and locks a journal. Another instance may remove an empty journal it found
+/// unlocked, so the file is opened again if that happened before the lock was taken.
+fn open_locked(path: &Path) -> std::io::Result<File> {
+    loop {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.lock()?;
+        if path.exists() {
+            return Ok(file);
+        }
+    }
+}
+
+/// Collects the journals left by sessions that did not shut down cleanly, moving each aside
+/// and returning their entries oldest first. Journals still locked by a running instance are
+/// left alone. A line cut off by the crash ends the replay of its journal.
+pub fn recover(config: &Config) -> color_eyre::Result<Vec<JournalEntry>> {
+    let mut entries: Vec<JournalEntry> = Vec::new();
+    for path in journal_files(config)? {
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "recovered")
+        {
+            continue;
+        }
+        let Ok(file) = File::open(&path) else {
+            continue;
+        };
+        // Locked by a running instance, or claimed by another one starting up
+        if file.try_lock().is_err() || !path.exists() {
+            continue;
+        }
+        let found: Vec<JournalEntry> = BufReader::new(&file)
+            .lines()
+            .map_while(|line| serde_json::from_str(&line.ok()?).ok())
+            .collect();
+        if found.is_empty() {
+            fs::remove_file(&path)
+                .wrap_err_with(|| format!("Failed to remove {}", path.display()))?;
+            continue;
+        }
+        let recovered = PathBuf::from(format!("{}.recovered", path.display()));
+        fs::rename(&path, &recovered)
+            .wrap_err_with(|| format!("Failed to move {}", path.display()))?;
+        entries.extend(found);
+    }
+    entries.sort_by_key(|entry| entry.at);
+    Ok(entries)
+}
+
+/// Removes the journals moved aside by [`recover`] once their entries have been replayed.
+pub fn discard_recovered(config: &Config) -> color_eyre::Result<()> {
+    for path in journal_files(config)? {
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "recovered")
+        {
+            fs::remove_file(&path)
+                .wrap_err_with(|| format!("Failed to remove {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// The start of every journal's path: the data file's, followed by `.journal.` and an ID
+/// for the instance writing it.
+fn journal_prefix(config: &Config) -> PathBuf {
+    PathBuf::from(format!("{}.journal.", config.storage.path))
+}
+
+/// Every journal next to the data file, including those moved aside.
+fn journal_files(config: &Config) -> color_eyre::Result<Vec<PathBuf>> {
+    let prefix = journal_prefix(config);
+    let dir = match prefix.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let Some(name) = prefix.file_name().and_then(|name| name.to_str()) else {
+        return Ok(Vec::new());
+    };
+    let listing = match fs::read_dir(&dir) {
+        Ok(listing) => listing,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).wrap_err_with(|| format!("Failed to read {}", dir.display())),
+    };
+    let mut paths: Vec<PathBuf> = listing
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|file| file.starts_with(name))
+        })
+        .map(|entry| dir.join(entry.file_name()))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::flat::FlatTask;
+    use crate::app::task::Task;
+
+    fn config(name: &str) -> Config {
+        let dir = std::env::temp_dir().join(format!("vinegar-journal-{}-{}", name, Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut config = Config::default();
+        config.storage.path = dir.join("tasks.json").display().to_string();
+        config
+    }
+
+    fn put() -> Vec<FlatOp> {
+        vec![FlatOp::Put(Box::new(FlatTask {
+            task: Task::new("Write report".to_string()),
+            parent: None,
+            position: 0,
+        }))]
+    }
+
+    #[test]
+    fn leaves_the_journal_of_a_running_instance_alone() {
+        let config = config("running");
+        let mut running = Journal::new(&config);
+        running.append(put()).unwrap();
+
+        assert!(recover(&config).unwrap().is_empty());
+
+        running.append(put()).unwrap();
+        running.clear().unwrap();
+        running.append(put()).unwrap();
+        assert!(recover(&config).unwrap().is_empty());
+        assert_eq!(journal_files(&config).unwrap(), vec![running.path.clone()]);
+    }
+
+    #[test]
+    fn recovers_the_journal_of_an_instance_that_crashed() {
+        let config = config("crashed");
+        let mut crashed = Journal::new(&config);
+        crashed.append(put()).unwrap();
+        crashed.append(put()).unwrap();
+        // Dropping it releases the lock without removing the file, as a crash would
+        drop(crashed);
+        let mut running = Journal::new(&config);
+        running.append(put()).unwrap();
+
+        assert_eq!(recover(&config).unwrap().len(), 2);
+        assert!(recover(&config).unwrap().is_empty());
+
+        discard_recovered(&config).unwrap();
+        assert_eq!(journal_files(&config).unwrap(), vec![running.path.clone()]);
+    }
+
+    #[test]
+    fn a_clean_shutdown_leaves_nothing_to_recover() {
+        let config = config("clean");
+        let mut journal = Journal::new(&config);
+        journal.append(put()).unwrap();
+        journal.close().unwrap();
+
+        assert!(journal_files(&config).unwrap().is_empty());
+        assert!(recover(&config).unwrap().is_empty());
+    }
+}
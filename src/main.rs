@@ -1,16 +1,78 @@
 use crate::app::App;
 
 pub mod app;
+pub mod backup;
+pub mod caldav;
 pub mod config;
+pub mod control;
 pub mod event;
+pub mod git;
+pub mod ical;
+pub mod journal;
+pub mod server;
+pub mod storage;
+pub mod taskwarrior;
 pub mod ui;
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
-    let config = config::load_config();
+    let mut config = config::load_config();
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--api") {
+        config.api.enabled = true;
+    }
+    if args.get(1).map(String::as_str) == Some("restore-backup") {
+        return backup::restore_command(&config, args.get(2).map(String::as_str));
+    }
+    if args.get(1).map(String::as_str) == Some("export-ics") {
+        return ical::export_command(&config, args.get(2).map(String::as_str));
+    }
+    if args.get(1).map(String::as_str) == Some("import-ics") {
+        return ical::import_command(&config, args.get(2).map(String::as_str));
+    }
+    if args.get(1).map(String::as_str) == Some("export-taskwarrior") {
+        return taskwarrior::export_command(&config, args.get(2).map(String::as_str));
+    }
+    if args.get(1).map(String::as_str) == Some("import-taskwarrior") {
+        return taskwarrior::import_command(&config, args.get(2).map(String::as_str));
+    }
+    if args.get(1).map(String::as_str) == Some("remote") {
+        if !control::remote_command(&config, &args[2..])? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.get(1).map(String::as_str) == Some("merge") {
+        // Git reads a failing exit status as "conflicts left"
+        if !app::merge::merge_command(&args[2..])? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    let (tasks, disk_version) = storage::load_tasks_versioned(&config)?;
+    let archive = storage::load_archive(&config)?;
+    let trash = storage::load_trash(&config)?;
+    let recovered = journal::recover(&config)?;
+    install_panic_hook();
     let terminal = ratatui::init();
-    let result = App::new(config).run(terminal).await;
+    let mut app = App::new(config, tasks, disk_version, archive, trash);
+    app.offer_recovery(recovered);
+    let result = app.run(terminal).await;
     ratatui::restore();
     result
 }
+
+/// Puts the terminal back before the panic message is printed. Every change is already in
+/// the journal by the time the next event is handled, so nothing else needs flushing; the
+/// next start offers to replay it.
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        ratatui::restore();
+        eprintln!(
+            "vinegar crashed. Unsaved changes will be offered for recovery on the next start."
+        );
+        default_hook(info);
+    }));
+}
@@ -0,0 +1,353 @@
+use crate::app::task::{Task, TaskId};
+use crate::app::task_utils;
+use crate::event::{AppEvent, Event};
+use color_eyre::eyre::{bail, WrapErr};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::UnboundedSender;
+use uuid::Uuid;
+
+/// Largest request body accepted, in bytes.
+const MAX_BODY: usize = 1024 * 1024;
+/// Most header lines read before a request is rejected.
+const MAX_HEADERS: usize = 100;
+/// Longest request or header line accepted, in bytes.
+const MAX_LINE: usize = 8 * 1024;
+/// Pause after a failed accept, such as when out of file descriptors, before trying again.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(250);
+
+/// The task tree as last published by the app, which requests read from.
+pub type SharedTasks = Arc<RwLock<Vec<Task>>>;
+
+/// Starts the HTTP/JSON API on `port` of the loopback interface and returns the address it
+/// listens on. Reads are answered from `tasks`; changes are sent to the app as events and
+/// show up in the TUI as they are handled. Every request must carry `token` as
+/// `Authorization: Bearer <token>` and name the loopback interface in its `Host` header,
+/// which keeps web pages and DNS rebinding from reaching the API through a browser.
+///
+/// ```text
+/// GET    /tasks                 the whole tree
+/// GET    /tasks/{id}            one task with its subtasks
+/// POST   /tasks                 {"input": "Buy milk #home", "parent": "{id}"}
+/// PATCH  /tasks/{id}            {"input": "Buy oat milk #home"}
+/// POST   /tasks/{id}/complete   {"force": true} to complete even if blocked
+/// POST   /tasks/{id}/reopen
+/// POST   /tasks/{id}/move       {"parent": "{id}"}, or null for the top level
+/// DELETE /tasks/{id}            moves the task to the trash
+/// ```
+///
+/// Changes are answered with `202 Accepted` once queued; adding a task also returns the ID it
+/// will have.
+pub fn start(
+    port: u16,
+    token: String,
+    sender: UnboundedSender<Event>,
+    tasks: SharedTasks,
+) -> color_eyre::Result<SocketAddr> {
+    if token.is_empty() {
+        bail!("set a token under [api] first");
+    }
+    let token: Arc<str> = token.into();
+    let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .wrap_err_with(|| format!("Failed to listen on port {}", port))?;
+    listener.set_nonblocking(true)?;
+    let listener = TcpListener::from_std(listener)?;
+    let address = listener.local_addr()?;
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            };
+            let (token, sender, tasks) = (token.clone(), sender.clone(), tasks.clone());
+            tokio::spawn(async move { handle_connection(stream, &token, sender, tasks).await });
+        }
+    });
+    Ok(address)
+}
+
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn accepted() -> Self {
+        Self {
+            status: 202,
+            body: json!({ "status": "accepted" }),
+        }
+    }
+
+    fn added(id: TaskId) -> Self {
+        Self {
+            status: 202,
+            body: json!({ "status": "accepted", "id": id }),
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+}
+
+/// Answers one request and closes the connection.
+async fn handle_connection(
+    stream: TcpStream,
+    token: &str,
+    sender: UnboundedSender<Event>,
+    tasks: SharedTasks,
+) {
+    let mut stream = BufReader::new(stream);
+    let response = match read_request(&mut stream).await {
+        Ok(request) if !is_loopback_host(request.host.as_deref()) => {
+            Response::error(403, "Host must be localhost or 127.0.0.1")
+        }
+        Ok(request) if !is_authorized(request.authorization.as_deref(), token) => {
+            Response::error(401, "Missing or wrong bearer token")
+        }
+        Ok(request) => route(&request, &sender, &tasks),
+        Err(err) => Response::error(400, err.to_string()),
+    };
+    let _ = write_response(stream.get_mut(), response).await;
+}
+
+/// Whether a `Host` header names the loopback interface. Browsers send the name the page
+/// used, so a rebound DNS name shows up here.
+fn is_loopback_host(host: Option<&str>) -> bool {
+    let Some(host) = host else {
+        return false;
+    };
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|byte| byte.is_ascii_digit()) => name,
+        _ => host,
+    };
+    name.eq_ignore_ascii_case("localhost") || name == "127.0.0.1"
+}
+
+/// Whether an `Authorization` header carries `token`, compared in constant time.
+fn is_authorized(authorization: Option<&str>, token: &str) -> bool {
+    let Some(given) = authorization.and_then(|value| value.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    let given = given.trim().as_bytes();
+    given.len() == token.len()
+        && given
+            .iter()
+            .zip(token.as_bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Reads one line into `line`, failing if it is longer than [`MAX_LINE`].
+async fn read_line(stream: &mut BufReader<TcpStream>, line: &mut String) -> color_eyre::Result<()> {
+    line.clear();
+    let read = (&mut *stream).take(MAX_LINE as u64).read_line(line).await?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        bail!("Line too long");
+    }
+    Ok(())
+}
+
+async fn read_request(stream: &mut BufReader<TcpStream>) -> color_eyre::Result<Request> {
+    let mut line = String::new();
+    read_line(stream, &mut line).await?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        bail!("Malformed request line");
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+    let mut content_length = 0;
+    let (mut host, mut authorization) = (None, None);
+    for _ in 0..MAX_HEADERS {
+        read_line(stream, &mut line).await?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            let mut body = vec![0; content_length];
+            stream.read_exact(&mut body).await?;
+            let path = target.split('?').next().unwrap_or_default().to_string();
+            return Ok(Request {
+                method,
+                path,
+                host,
+                authorization,
+                body,
+            });
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().wrap_err("Bad Content-Length")?;
+                if content_length > MAX_BODY {
+                    bail!("Request body too large");
+                }
+            } else if name.eq_ignore_ascii_case("host") {
+                host = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+    bail!("Too many headers")
+}
+
+async fn write_response(stream: &mut TcpStream, response: Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        _ => "Service Unavailable",
+    };
+    let body = response.body.to_string();
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[derive(Deserialize)]
+struct NewTask {
+    input: String,
+    #[serde(default)]
+    parent: Option<TaskId>,
+}
+
+#[derive(Deserialize)]
+struct TaskUpdate {
+    input: String,
+}
+
+#[derive(Deserialize, Default)]
+struct Completion {
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize)]
+struct Move {
+    parent: Option<TaskId>,
+}
+
+fn route(request: &Request, sender: &UnboundedSender<Event>, tasks: &SharedTasks) -> Response {
+    let tasks = match tasks.read() {
+        Ok(tasks) => tasks.clone(),
+        Err(_) => return Response::error(503, "Task tree unavailable"),
+    };
+    let index = task_utils::build_index(&tasks);
+    let find = |id: &str| -> Result<&Task, Response> {
+        let not_found = || Response::error(404, format!("No task {}", id));
+        let id: TaskId = id.parse().map_err(|_| not_found())?;
+        index
+            .get(&id)
+            .and_then(|path| task_utils::get_task(&tasks, path))
+            .ok_or_else(not_found)
+    };
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let result = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["tasks"]) => Ok(Response::ok(json!(tasks))),
+        ("GET", ["tasks", id]) => find(id).map(|task| Response::ok(json!(task))),
+        ("POST", ["tasks"]) => parse_body::<NewTask>(&request.body).and_then(|new_task| {
+            if let Some(parent) = new_task.parent {
+                find(&parent.to_string())?;
+            }
+            let id = Uuid::new_v4();
+            send(
+                sender,
+                AppEvent::AddTask {
+                    id,
+                    parent: new_task.parent,
+                    input: new_task.input,
+                },
+            )
+            .map(|_| Response::added(id))
+        }),
+        ("PATCH", ["tasks", id]) => find(id).and_then(|task| {
+            let update = parse_body::<TaskUpdate>(&request.body)?;
+            send(
+                sender,
+                AppEvent::UpdateTask {
+                    id: task.id,
+                    input: update.input,
+                },
+            )
+        }),
+        ("POST", ["tasks", id, action @ ("complete" | "reopen")]) => find(id).and_then(|task| {
+            let completion = if request.body.is_empty() {
+                Completion::default()
+            } else {
+                parse_body::<Completion>(&request.body)?
+            };
+            // Completion toggles, so only send it when the task is not already as asked
+            if task.completed == (*action == "complete") {
+                return Ok(Response::accepted());
+            }
+            send(
+                sender,
+                AppEvent::ToggleComplete {
+                    ids: vec![task.id],
+                    force: completion.force,
+                },
+            )
+        }),
+        ("POST", ["tasks", id, "move"]) => find(id).and_then(|task| {
+            let target = parse_body::<Move>(&request.body)?;
+            if let Some(parent) = target.parent {
+                find(&parent.to_string())?;
+            }
+            send(
+                sender,
+                AppEvent::MoveTasks {
+                    ids: vec![task.id],
+                    parent: target.parent,
+                },
+            )
+        }),
+        ("DELETE", ["tasks", id]) => {
+            find(id).and_then(|task| send(sender, AppEvent::DeleteTasks { ids: vec![task.id] }))
+        }
+        _ => Err(Response::error(
+            404,
+            format!("No route for {} {}", request.method, request.path),
+        )),
+    };
+    result.unwrap_or_else(|response| response)
+}
+
+fn parse_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, Response> {
+    serde_json::from_slice(body).map_err(|err| Response::error(400, format!("Bad body: {}", err)))
+}
+
+fn send(sender: &UnboundedSender<Event>, event: AppEvent) -> Result<Response, Response> {
+    sender
+        .send(Event::App(event))
+        .map(|()| Response::accepted())
+        .map_err(|_| Response::error(503, "vinegar is shutting down"))
+}
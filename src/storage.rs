@@ -0,0 +1,218 @@
+use crate::app::archive::ArchivedTask;
+use crate::app::task::Task;
+use crate::app::trash::TrashedTask;
+use crate::config::Config;
+use color_eyre::eyre::WrapErr;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How long after a write its modification time can still be shared with a later write.
+const MTIME_GRANULARITY: Duration = Duration::from_secs(2);
+
+/// What a file looked like when it was last read or written, to notice when another process
+/// changes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FileVersion {
+    pub modified: Option<SystemTime>,
+    /// Hash of the contents; `None` if the file does not exist.
+    pub hash: Option<u64>,
+}
+
+impl FileVersion {
+    fn new(path: &Path, data: Option<&[u8]>) -> Self {
+        Self {
+            modified: fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            hash: data.map(|data| {
+                let mut hasher = DefaultHasher::new();
+                data.hash(&mut hasher);
+                hasher.finish()
+            }),
+        }
+    }
+}
+
+/// Loads the task tree from the data file, or an empty tree if there is none yet.
+pub fn load_tasks(config: &Config) -> color_eyre::Result<Vec<Task>> {
+    load_tasks_versioned(config).map(|(tasks, _)| tasks)
+}
+
+/// Loads the task tree along with the version of the data file it was read from.
+pub fn load_tasks_versioned(config: &Config) -> color_eyre::Result<(Vec<Task>, FileVersion)> {
+    let path = Path::new(&config.storage.path);
+    if !path.exists() {
+        return Ok((Vec::new(), FileVersion::default()));
+    }
+    let data = fs::read(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    let version = FileVersion::new(path, Some(&data));
+    let mut tasks: Vec<Task> = serde_json::from_slice(&data)
+        .wrap_err_with(|| format!("Failed to parse {}", path.display()))?;
+    if !config.view.remember_expanded {
+        for task in &mut tasks {
+            task.set_expanded_recursive(false);
+        }
+    }
+    Ok((tasks, version))
+}
+
+/// Writes the task tree to the data file and returns the version written.
+pub fn save_tasks(config: &Config, tasks: &[Task]) -> color_eyre::Result<FileVersion> {
+    let path = Path::new(&config.storage.path);
+    let data = write_json(path, tasks)?;
+    Ok(FileVersion::new(path, Some(data.as_bytes())))
+}
+
+/// Whether the data file no longer matches `known`. Only hashes the contents when the
+/// modification time moved, and remembers the new time if the contents turn out the same.
+pub fn data_file_changed(config: &Config, known: &mut FileVersion) -> color_eyre::Result<bool> {
+    let path = Path::new(&config.storage.path);
+    let current = FileVersion::new(path, None);
+    // Two writes within the timestamp granularity leave the same time behind, so a recent
+    // time is not trusted on its own
+    let settled = current
+        .modified
+        .is_some_and(|modified| modified.elapsed().is_ok_and(|age| age >= MTIME_GRANULARITY));
+    if settled && current.modified == known.modified && known.hash.is_some() {
+        return Ok(false);
+    }
+    let data = match fs::read(path) {
+        Ok(data) => Some(data),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err).wrap_err_with(|| format!("Failed to read {}", path.display())),
+    };
+    let current = FileVersion::new(path, data.as_deref());
+    if current.hash == known.hash {
+        known.modified = current.modified;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Loads the archived tasks, or an empty archive if there is none yet.
+pub fn load_archive(config: &Config) -> color_eyre::Result<Vec<ArchivedTask>> {
+    read_json(Path::new(&config.archive.path))
+}
+
+/// Writes the archived tasks to the archive file.
+pub fn save_archive(config: &Config, archive: &[ArchivedTask]) -> color_eyre::Result<()> {
+    write_json(Path::new(&config.archive.path), archive)?;
+    Ok(())
+}
+
+/// Loads the deleted tasks, or an empty trash if there is none yet.
+pub fn load_trash(config: &Config) -> color_eyre::Result<Vec<TrashedTask>> {
+    read_json(Path::new(&config.trash.path))
+}
+
+/// Writes the deleted tasks to the trash file.
+pub fn save_trash(config: &Config, trash: &[TrashedTask]) -> color_eyre::Result<()> {
+    write_json(Path::new(&config.trash.path), trash)?;
+    Ok(())
+}
+
+pub fn read_json<T: DeserializeOwned + Default>(path: &Path) -> color_eyre::Result<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let data =
+        fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&data).wrap_err_with(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Writes to a temporary file and renames it over `path`, so a crash mid-write leaves the
+/// previous version intact. Returns the JSON written.
+pub fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> color_eyre::Result<String> {
+    let data = serde_json::to_string_pretty(value)?;
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+    let mut file = File::create(&temp_path)
+        .wrap_err_with(|| format!("Failed to write {}", temp_path.display()))?;
+    file.write_all(data.as_bytes())
+        .and_then(|()| file.sync_all())
+        .wrap_err_with(|| format!("Failed to write {}", temp_path.display()))?;
+    fs::rename(&temp_path, path)
+        .wrap_err_with(|| format!("Failed to replace {}", path.display()))?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::recurrence::Recurrence;
+    use crate::app::task::{Note, Priority};
+    use crate::app::timer::TimeEntry;
+    use chrono::{Local, NaiveDate, TimeDelta};
+
+    fn config() -> Config {
+        let dir = std::env::temp_dir().join(format!("vinegar-storage-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut config = Config::default();
+        config.storage.path = dir.join("tasks.json").display().to_string();
+        config.trash.path = dir.join("trash.json").display().to_string();
+        config
+    }
+
+    fn tree() -> Vec<Task> {
+        let mut parent = Task::new("Plan trip".to_string());
+        parent.expanded = true;
+        parent.tags = vec!["travel".to_string()];
+        parent.priority = Some(Priority::High);
+        parent.due = NaiveDate::from_ymd_opt(2024, 7, 1);
+        parent.recurrence = Some(Recurrence::Monthly { day: Some(31) });
+        parent.notes = vec![Note {
+            added: Local::now(),
+            text: "Ask about visas".to_string(),
+        }];
+        let mut child = Task::new("Book flights".to_string());
+        child.set_completed(true);
+        let end = Local::now();
+        child.time_log = vec![TimeEntry {
+            start: end - TimeDelta::minutes(25),
+            end,
+        }];
+        let mut blocked = Task::new("Pack".to_string());
+        blocked.blocked_by = vec![child.id];
+        parent.subtasks = vec![child, blocked];
+        vec![parent, Task::new("Water plants".to_string())]
+    }
+
+    #[test]
+    fn saved_tasks_load_back_unchanged() {
+        let config = config();
+        let tasks = tree();
+
+        let saved = save_tasks(&config, &tasks).unwrap();
+        let (loaded, version) = load_tasks_versioned(&config).unwrap();
+
+        assert_eq!(loaded, tasks);
+        assert_eq!(version.hash, saved.hash);
+        let mut known = version;
+        assert!(!data_file_changed(&config, &mut known).unwrap());
+        assert!(!Path::new(&format!("{}.tmp", config.storage.path)).exists());
+    }
+
+    #[test]
+    fn expanded_state_is_reset_unless_remembered() {
+        let mut config = config();
+        config.view.remember_expanded = false;
+        save_tasks(&config, &tree()).unwrap();
+
+        let loaded = load_tasks(&config).unwrap();
+
+        assert!(!loaded[0].expanded);
+    }
+
+    #[test]
+    fn a_missing_file_loads_as_an_empty_tree() {
+        let config = config();
+        assert!(load_tasks(&config).unwrap().is_empty());
+        assert!(load_trash(&config).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,441 @@
+use crate::app::flat::{self, FlatTask, FlatTree};
+use crate::app::task::{Note, Priority, Task, TaskId};
+use crate::config::Config;
+use crate::storage;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+use color_eyre::eyre::{eyre, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use uuid::Uuid;
+
+/// How Taskwarrior writes timestamps, always in UTC.
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// A task in Taskwarrior's JSON format, as written by `task export` and read by `task import`.
+/// Attributes vinegar has no use for, such as `urgency`, are ignored.
+#[derive(Debug, Serialize, Deserialize)]
+struct TwTask {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    uuid: Option<Uuid>,
+    description: String,
+    #[serde(default)]
+    status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    entry: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+    /// When the task was completed or deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// Dotted path such as `work.backend`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    annotations: Vec<TwAnnotation>,
+    #[serde(default, skip_serializing_if = "Depends::is_empty")]
+    depends: Depends,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TwAnnotation {
+    entry: String,
+    description: String,
+}
+
+/// UUIDs of the tasks a task depends on. Taskwarrior 2.6 and later write a list; earlier
+/// versions a comma-separated string.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Depends {
+    List(Vec<String>),
+    Joined(String),
+}
+
+impl Default for Depends {
+    fn default() -> Self {
+        Depends::List(Vec::new())
+    }
+}
+
+impl Depends {
+    fn is_empty(&self) -> bool {
+        self.ids().is_empty()
+    }
+
+    fn ids(&self) -> Vec<TaskId> {
+        let parse = |id: &str| Uuid::parse_str(id.trim()).ok();
+        match self {
+            Depends::List(ids) => ids.iter().filter_map(|id| parse(id)).collect(),
+            Depends::Joined(ids) => ids.split(',').filter_map(parse).collect(),
+        }
+    }
+}
+
+/// The tree as Taskwarrior tasks. Where a task sits becomes its `project`, the names of its
+/// ancestors joined with dots, and its blockers become `depends`.
+fn to_taskwarrior(tasks: &[Task]) -> Vec<TwTask> {
+    let mut exported = Vec::new();
+    let mut stack: Vec<(&Task, Vec<String>)> =
+        tasks.iter().rev().map(|task| (task, Vec::new())).collect();
+    while let Some((task, project)) = stack.pop() {
+        let mut path = project.clone();
+        path.push(project_segment(&task.name));
+        stack.extend(
+            task.subtasks
+                .iter()
+                .rev()
+                .map(|subtask| (subtask, path.clone())),
+        );
+        exported.push(TwTask {
+            uuid: Some(task.id),
+            description: task.name.clone(),
+            status: if task.completed {
+                "completed"
+            } else {
+                "pending"
+            }
+            .to_string(),
+            entry: Some(format_date_time(task.created)),
+            modified: Some(format_date_time(task.modified)),
+            end: task.completed_at.map(format_date_time),
+            // Taskwarrior keeps a due date as the local midnight it starts at
+            due: task
+                .due
+                .and_then(|due| {
+                    due.and_hms_opt(0, 0, 0)?
+                        .and_local_timezone(Local)
+                        .earliest()
+                })
+                .map(format_date_time),
+            priority: task.priority.map(|priority| {
+                match priority {
+                    Priority::High => "H",
+                    Priority::Medium => "M",
+                    Priority::Low => "L",
+                }
+                .to_string()
+            }),
+            tags: task.tags.clone(),
+            project: (!project.is_empty()).then(|| project.join(".")),
+            annotations: task
+                .notes
+                .iter()
+                .map(|note| TwAnnotation {
+                    entry: format_date_time(note.added),
+                    description: note.text.clone(),
+                })
+                .collect(),
+            depends: Depends::List(task.blocked_by.iter().map(Uuid::to_string).collect()),
+        });
+    }
+    exported
+}
+
+/// A vinegar task from a Taskwarrior one, without its place in the tree.
+fn from_taskwarrior(tw_task: &TwTask) -> Task {
+    let mut task = Task::new(tw_task.description.clone());
+    task.id = tw_task.uuid.unwrap_or_else(Uuid::new_v4);
+    let parse = |value: &Option<String>| value.as_deref().and_then(parse_date_time);
+    if let Some(entry) = parse(&tw_task.entry) {
+        task.created = entry;
+    }
+    if tw_task.status == "completed" {
+        task.completed = true;
+        task.completed_at = Some(parse(&tw_task.end).unwrap_or(task.created));
+    }
+    task.due = parse(&tw_task.due).map(|due| due.date_naive());
+    task.priority = match tw_task.priority.as_deref() {
+        Some("H") => Some(Priority::High),
+        Some("M") => Some(Priority::Medium),
+        Some("L") => Some(Priority::Low),
+        _ => None,
+    };
+    task.tags = tw_task.tags.clone();
+    task.notes = tw_task
+        .annotations
+        .iter()
+        .map(|annotation| Note {
+            added: parse_date_time(&annotation.entry).unwrap_or(task.created),
+            text: annotation.description.clone(),
+        })
+        .collect();
+    task.blocked_by = tw_task.depends.ids();
+    task.modified = parse(&tw_task.modified).unwrap_or(task.created);
+    task
+}
+
+/// Adds Taskwarrior tasks to the tree and returns how many were added. Each dotted project
+/// becomes a chain of parent tasks, reusing tasks already at that place with the same name,
+/// whether they were in the tree or are among those imported; missing ones are created.
+/// Tasks already in the tree are skipped, and so are deleted tasks and recurring templates,
+/// whose pending instances are imported instead.
+fn import_into(tasks: &mut Vec<Task>, tw_tasks: &[TwTask]) -> usize {
+    let mut tree = flat::flatten(tasks);
+    let existing = tree.len();
+    // Every task by the names from the top down to itself
+    let mut by_path: HashMap<Vec<String>, TaskId> = HashMap::new();
+    for &id in tree.keys() {
+        by_path.entry(name_path(&tree, id)).or_insert(id);
+    }
+    let imported: Vec<(Task, Vec<String>)> = tw_tasks
+        .iter()
+        .filter(|tw_task| !matches!(tw_task.status.as_str(), "deleted" | "recurring"))
+        .map(|tw_task| {
+            let project = tw_task
+                .project
+                .iter()
+                .flat_map(|project| project.split('.'))
+                .map(str::trim)
+                .filter(|segment| !segment.is_empty())
+                .map(str::to_string)
+                .collect();
+            (from_taskwarrior(tw_task), project)
+        })
+        .filter(|(task, _)| !tree.contains_key(&task.id))
+        .collect();
+    for (task, project) in &imported {
+        let mut path = project.clone();
+        path.push(project_segment(&task.name));
+        by_path.entry(path).or_insert(task.id);
+    }
+    let imported_ids: HashSet<TaskId> = imported.iter().map(|(task, _)| task.id).collect();
+    // Keep the import's order after the tasks already there
+    let mut position = existing;
+    for (task, project) in imported {
+        let parent = parent_for(&project, &mut tree, &mut by_path, &mut position);
+        tree.insert(
+            task.id,
+            FlatTask {
+                task,
+                parent,
+                position,
+            },
+        );
+        position += 1;
+    }
+    // Dependencies of imported tasks on tasks that were not imported would block forever;
+    // those of tasks already there are none of the import's business
+    let ids: HashSet<TaskId> = tree.keys().copied().collect();
+    for id in &imported_ids {
+        if let Some(entry) = tree.get_mut(id) {
+            entry.task.blocked_by.retain(|id| ids.contains(id));
+        }
+    }
+    let added = tree.len() - existing;
+    *tasks = flat::unflatten(&tree);
+    added
+}
+
+/// The task standing for `project`, creating it and any missing ancestors.
+fn parent_for(
+    project: &[String],
+    tree: &mut FlatTree,
+    by_path: &mut HashMap<Vec<String>, TaskId>,
+    position: &mut usize,
+) -> Option<TaskId> {
+    let (name, ancestors) = project.split_last()?;
+    if let Some(&id) = by_path.get(project) {
+        return Some(id);
+    }
+    let parent = parent_for(ancestors, tree, by_path, position);
+    let task = Task::new(name.clone());
+    let id = task.id;
+    tree.insert(
+        id,
+        FlatTask {
+            task,
+            parent,
+            position: *position,
+        },
+    );
+    *position += 1;
+    by_path.insert(project.to_vec(), id);
+    Some(id)
+}
+
+/// The project segments naming a task and its ancestors.
+fn name_path(tree: &FlatTree, id: TaskId) -> Vec<String> {
+    let mut path = Vec::new();
+    let mut current = tree.get(&id);
+    while let Some(entry) = current {
+        path.push(project_segment(&entry.task.name));
+        // A parent cycle would never end; stop once it has gone around the tree
+        if path.len() > tree.len() {
+            break;
+        }
+        current = entry.parent.and_then(|parent| tree.get(&parent));
+    }
+    path.reverse();
+    path
+}
+
+/// A task name as one segment of a Taskwarrior project, where dots separate levels.
+fn project_segment(name: &str) -> String {
+    name.trim().replace('.', "_")
+}
+
+fn format_date_time(time: DateTime<Local>) -> String {
+    time.with_timezone(&Utc)
+        .format(DATE_TIME_FORMAT)
+        .to_string()
+}
+
+/// A Taskwarrior timestamp, also accepting RFC 3339 and plain dates.
+fn parse_date_time(value: &str) -> Option<DateTime<Local>> {
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT) {
+        return Some(time.and_utc().with_timezone(&Local));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Local));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    date.and_hms_opt(0, 0, 0)?
+        .and_local_timezone(Local)
+        .earliest()
+}
+
+/// The `export-taskwarrior` command: writes the whole tree to a JSON file `task import`
+/// reads.
+pub fn export_command(config: &Config, path: Option<&str>) -> color_eyre::Result<()> {
+    let path = path.ok_or_else(|| eyre!("Usage: vinegar export-taskwarrior <file.json>"))?;
+    let tasks = storage::load_tasks(config)?;
+    let exported = to_taskwarrior(&tasks);
+    let json = serde_json::to_string_pretty(&exported)?;
+    fs::write(path, json).wrap_err_with(|| format!("Failed to write {}", path))?;
+    println!("Exported {} task(s) to {}", exported.len(), path);
+    Ok(())
+}
+
+/// The `import-taskwarrior` command: adds the tasks in a file written by `task export` to the
+/// tree. A running instance picks the result up like any other change to the data file.
+pub fn import_command(config: &Config, path: Option<&str>) -> color_eyre::Result<()> {
+    let path = path.ok_or_else(|| eyre!("Usage: vinegar import-taskwarrior <file.json>"))?;
+    let json = fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path))?;
+    let tw_tasks: Vec<TwTask> =
+        serde_json::from_str(&json).wrap_err_with(|| format!("Failed to parse {}", path))?;
+    let mut tasks = storage::load_tasks(config)?;
+    let added = import_into(&mut tasks, &tw_tasks);
+    storage::save_tasks(config, &tasks)?;
+    println!(
+        "Imported {} task(s) from {} into {}",
+        added, path, config.storage.path
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tw_task(description: &str, project: Option<&str>) -> TwTask {
+        TwTask {
+            uuid: Some(Uuid::new_v4()),
+            description: description.to_string(),
+            status: "pending".to_string(),
+            entry: None,
+            modified: None,
+            end: None,
+            due: None,
+            priority: None,
+            tags: Vec::new(),
+            project: project.map(str::to_string),
+            annotations: Vec::new(),
+            depends: Depends::default(),
+        }
+    }
+
+    #[test]
+    fn exports_ancestors_as_a_dotted_project() {
+        let mut work = Task::new("work".to_string());
+        let mut backend = Task::new("backend v2.0".to_string());
+        backend.subtasks.push(Task::new("Fix login".to_string()));
+        work.subtasks.push(backend);
+
+        let exported = to_taskwarrior(&[work]);
+
+        let projects: Vec<(&str, Option<&str>)> = exported
+            .iter()
+            .map(|task| (task.description.as_str(), task.project.as_deref()))
+            .collect();
+        assert_eq!(
+            projects,
+            [
+                ("work", None),
+                ("backend v2.0", Some("work")),
+                ("Fix login", Some("work.backend v2_0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_dotted_projects_as_parents_reusing_existing_ones() {
+        let mut work = Task::new("work".to_string());
+        work.subtasks.push(Task::new("backend".to_string()));
+        let mut tasks = vec![work.clone()];
+        let tw_tasks = [
+            tw_task("Fix login", Some("work.backend")),
+            tw_task("Write docs", Some("work.frontend")),
+            tw_task("Water plants", None),
+        ];
+
+        let added = import_into(&mut tasks, &tw_tasks);
+
+        // The missing `frontend` is created along with the three tasks
+        assert_eq!(added, 4);
+        let names = |tasks: &[Task]| -> Vec<String> {
+            tasks.iter().map(|task| task.name.clone()).collect()
+        };
+        assert_eq!(names(&tasks), ["work", "Water plants"]);
+        assert_eq!(tasks[0].id, work.id);
+        assert_eq!(names(&tasks[0].subtasks), ["backend", "frontend"]);
+        assert_eq!(names(&tasks[0].subtasks[0].subtasks), ["Fix login"]);
+        assert_eq!(names(&tasks[0].subtasks[1].subtasks), ["Write docs"]);
+    }
+
+    #[test]
+    fn exported_projects_import_back_into_the_same_tree() {
+        let mut work = Task::new("work".to_string());
+        let mut backend = Task::new("backend".to_string());
+        backend.subtasks.push(Task::new("Fix login".to_string()));
+        work.subtasks.push(backend);
+        let exported = to_taskwarrior(&[work]);
+        // Without UUIDs the tasks are new, so the tree is rebuilt from the projects alone
+        let exported: Vec<TwTask> = exported
+            .into_iter()
+            .map(|task| TwTask { uuid: None, ..task })
+            .collect();
+
+        let mut tasks = Vec::new();
+        import_into(&mut tasks, &exported);
+
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].name, "work");
+        assert_eq!(tasks[0].subtasks.len(), 1);
+        assert_eq!(tasks[0].subtasks[0].name, "backend");
+        assert_eq!(tasks[0].subtasks[0].subtasks[0].name, "Fix login");
+    }
+
+    #[test]
+    fn drops_unknown_dependencies_of_imported_tasks_only() {
+        let outside = Uuid::new_v4();
+        let mut existing = Task::new("Ship release".to_string());
+        existing.blocked_by = vec![outside];
+        let mut tasks = vec![existing];
+        let blocker = tw_task("Write changelog", None);
+        let mut blocked = tw_task("Tag release", None);
+        blocked.depends =
+            Depends::List(vec![blocker.uuid.unwrap().to_string(), outside.to_string()]);
+
+        import_into(&mut tasks, &[blocker, blocked]);
+
+        assert_eq!(tasks[0].blocked_by, vec![outside]);
+        assert_eq!(tasks[2].blocked_by, vec![tasks[1].id]);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::app::{task::Task, App};
+use crate::ui::utils::task_spans;
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, List, ListItem, Paragraph},
+    Frame,
+};
+
+/// Draws the backup list beside a preview of the selected backup's tree.
+pub fn render_backups(frame: &mut Frame, area: Rect, app: &mut App, highlight: Style) {
+    let [list_area, preview_area] =
+        Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(area);
+
+    let items: Vec<ListItem> = app
+        .backups
+        .iter()
+        .map(|backup| ListItem::new(backup.created.format("%Y-%m-%d %H:%M:%S").to_string()))
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .title(format!("Backups ({})", app.backups.len()))
+                .title_alignment(Alignment::Center)
+                .border_type(BorderType::Rounded),
+        )
+        .highlight_style(highlight)
+        .highlight_symbol(app.config.theme.icons.highlight_symbol.as_str());
+    frame.render_stateful_widget(list, list_area, &mut app.backup_list_state);
+
+    let mut lines = Vec::new();
+    preview_lines(&app.backup_preview, 0, &mut lines);
+    if lines.is_empty() {
+        lines.push(Line::styled(
+            "No tasks in this backup",
+            Style::default().add_modifier(Modifier::DIM),
+        ));
+    }
+    let preview = Paragraph::new(lines).block(
+        Block::bordered()
+            .title("Preview")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded),
+    );
+    frame.render_widget(preview, preview_area);
+}
+
+/// The whole tree, fully expanded, one task per line.
+fn preview_lines(tasks: &[Task], depth: usize, lines: &mut Vec<Line<'static>>) {
+    for task in tasks {
+        let checkbox = if task.completed { "[x]" } else { "[ ]" };
+        let mut spans = vec![Span::raw(format!("{}{} ", "  ".repeat(depth), checkbox))];
+        spans.extend(task_spans(task));
+        lines.push(Line::from(spans));
+        preview_lines(&task.subtasks, depth + 1, lines);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::app::{flat::FlatTask, App};
+use crate::ui::utils::task_spans;
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, BorderType, List, ListItem, Paragraph},
+    Frame,
+};
+
+/// Draws the merge conflicts beside both versions of the selected task.
+pub fn render_conflicts(frame: &mut Frame, area: Rect, app: &mut App, highlight: Style) {
+    let [list_area, ours_area, theirs_area] = Layout::horizontal([
+        Constraint::Percentage(40),
+        Constraint::Percentage(30),
+        Constraint::Percentage(30),
+    ])
+    .areas(area);
+
+    let items: Vec<ListItem> = app
+        .conflicts
+        .iter()
+        .map(|conflict| ListItem::new(conflict.describe()))
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .title(format!("Merge conflicts ({})", app.conflicts.len()))
+                .title_alignment(Alignment::Center)
+                .border_type(BorderType::Rounded),
+        )
+        .highlight_style(highlight)
+        .highlight_symbol(app.config.theme.icons.highlight_symbol.as_str());
+    frame.render_stateful_widget(list, list_area, &mut app.conflict_list_state);
+
+    let selected = app.conflict_list_state.selected();
+    let Some(conflict) = selected.and_then(|index| app.conflicts.get(index)) else {
+        return;
+    };
+    let sides = [
+        ("Here", conflict.ours.as_ref(), ours_area),
+        ("There", conflict.theirs.as_ref(), theirs_area),
+    ];
+    for (title, version, side_area) in sides {
+        let version = Paragraph::new(version_lines(app, version)).block(
+            Block::bordered()
+                .title(title)
+                .title_alignment(Alignment::Center)
+                .border_type(BorderType::Rounded),
+        );
+        frame.render_widget(version, side_area);
+    }
+}
+
+/// One side's version of a conflicting task.
+fn version_lines(app: &App, version: Option<&FlatTask>) -> Vec<Line<'static>> {
+    let Some(entry) = version else {
+        return vec![Line::styled(
+            "Deleted",
+            Style::default().add_modifier(Modifier::DIM),
+        )];
+    };
+    let parent = match entry.parent {
+        Some(parent) => app.task(parent).map_or_else(
+            || "a deleted task".to_string(),
+            |task| format!("'{}'", task.name),
+        ),
+        None => "the top level".to_string(),
+    };
+    let status = if entry.task.completed {
+        "Completed"
+    } else {
+        "Open"
+    };
+    vec![
+        Line::from(task_spans(&entry.task)),
+        Line::from(""),
+        Line::from(format!("Under: {}", parent)),
+        Line::from(format!("Status: {}", status)),
+        Line::from(format!(
+            "Modified: {}",
+            entry.task.modified.format("%Y-%m-%d %H:%M")
+        )),
+    ]
+}
@@ -0,0 +1,93 @@
+use crate::app::{task::Task, timer::format_duration, App};
+use chrono::{DateTime, Local};
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Paragraph, Wrap},
+    Frame,
+};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Draws the attributes of the selected task into `area`.
+pub fn render_details(frame: &mut Frame, area: Rect, app: &App) {
+    let block = Block::bordered()
+        .title("Details")
+        .title_alignment(Alignment::Center)
+        .border_type(BorderType::Rounded);
+    let lines = match app.selected_id().and_then(|id| app.task(id)) {
+        Some(task) => detail_lines(app, task),
+        None => vec![Line::from("No task selected")],
+    };
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, area);
+}
+
+fn detail_lines<'a>(app: &App, task: &'a Task) -> Vec<Line<'a>> {
+    let mut lines = vec![
+        Line::styled(
+            task.name.as_str(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Line::default(),
+    ];
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|tag| format!("#{}", tag)).collect();
+        lines.push(field("Tags", tags.join(" ")));
+    }
+    if let Some(priority) = task.priority {
+        lines.push(field("Priority", priority.as_str().to_string()));
+    }
+    if let Some(due) = task.due {
+        lines.push(field("Due", due.to_string()));
+    }
+    if let Some(recurrence) = &task.recurrence {
+        lines.push(field("Repeats", recurrence.describe()));
+    }
+    let blockers = app.open_blockers(task);
+    if !blockers.is_empty() {
+        let names: Vec<&str> = blockers
+            .iter()
+            .map(|blocker| blocker.name.as_str())
+            .collect();
+        lines.push(field("Blocked by", names.join(", ")));
+    }
+    if !task.subtasks.is_empty() {
+        lines.push(field("Subtasks", task.descendant_count().to_string()));
+    }
+    let logged = app.total_logged(task);
+    if !logged.is_zero() {
+        lines.push(field("Logged", format_duration(logged)));
+    }
+    if task.pomodoros > 0 {
+        lines.push(field("Pomodoros", task.pomodoros.to_string()));
+    }
+    for note in &task.notes {
+        let added = note.added.format("%Y-%m-%d");
+        lines.push(field("Note", format!("{} {}", added, note.text)));
+    }
+    lines.push(field("Created", format_timestamp(task.created)));
+    lines.push(field("Modified", format_timestamp(task.modified)));
+    if let Some(completed_at) = task.completed_at {
+        lines.push(field("Completed", format_timestamp(completed_at)));
+    }
+    lines
+}
+
+/// A `label: value` line with the label dimmed.
+fn field(label: &str, value: String) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(
+            format!("{:<10}", format!("{}:", label)),
+            Style::default().add_modifier(Modifier::DIM),
+        ),
+        Span::raw(value),
+    ])
+}
+
+fn format_timestamp(timestamp: DateTime<Local>) -> String {
+    timestamp.format(TIMESTAMP_FORMAT).to_string()
+}
@@ -0,0 +1,56 @@
+use crate::app::App;
+use ratatui::{
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, BorderType, List, ListItem, Paragraph},
+    Frame,
+};
+
+/// Draws the commits of the data file beside the changes made in the selected one.
+pub fn render_history(frame: &mut Frame, area: Rect, app: &mut App, highlight: Style) {
+    let [list_area, changes_area] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(area);
+
+    let items: Vec<ListItem> = app
+        .history
+        .iter()
+        .map(|commit| {
+            ListItem::new(format!(
+                "{} {} {}",
+                commit.short_hash(),
+                commit.time.format("%Y-%m-%d %H:%M"),
+                commit.subject
+            ))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .title(format!("History ({})", app.history.len()))
+                .title_alignment(Alignment::Center)
+                .border_type(BorderType::Rounded),
+        )
+        .highlight_style(highlight)
+        .highlight_symbol(app.config.theme.icons.highlight_symbol.as_str());
+    frame.render_stateful_widget(list, list_area, &mut app.history_list_state);
+
+    let mut lines: Vec<Line> = app
+        .history_changes
+        .iter()
+        .map(|change| Line::from(change.as_str()))
+        .collect();
+    if lines.is_empty() {
+        lines.push(Line::styled(
+            "No changes to tasks in this commit",
+            Style::default().add_modifier(Modifier::DIM),
+        ));
+    }
+    let changes = Paragraph::new(lines).block(
+        Block::bordered()
+            .title("Changes")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded),
+    );
+    frame.render_widget(changes, changes_area);
+}
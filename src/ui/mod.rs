@@ -1,2 +1,8 @@
+pub mod backups;
+pub mod conflicts;
+pub mod details;
+pub mod history;
+pub mod removed;
+#[allow(clippy::module_inception)]
 pub mod ui;
 pub mod utils;
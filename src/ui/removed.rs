@@ -0,0 +1,94 @@
+use crate::app::{location::Location, task::Task, App};
+use crate::ui::utils::task_spans;
+use chrono::{DateTime, Local};
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, List, ListItem, ListState},
+    Frame,
+};
+
+/// Draws the archive browser into `area`, in place of the task list.
+pub fn render_archive(frame: &mut Frame, area: Rect, app: &mut App, highlight: Style) {
+    let items = app
+        .archive
+        .iter()
+        .map(|entry| entry_item(&entry.task, &entry.location, "archived", entry.archived_at))
+        .collect();
+    let title = format!("Archive ({} task(s))", app.archive.len());
+    let symbol = app.config.theme.icons.highlight_symbol.clone();
+    render_entries(
+        frame,
+        area,
+        title,
+        items,
+        highlight,
+        &symbol,
+        &mut app.archive_list_state,
+    );
+}
+
+/// Draws the trash browser into `area`, in place of the task list.
+pub fn render_trash(frame: &mut Frame, area: Rect, app: &mut App, highlight: Style) {
+    let items = app
+        .trash
+        .iter()
+        .map(|entry| entry_item(&entry.task, &entry.location, "deleted", entry.deleted_at))
+        .collect();
+    let title = format!("Trash ({} task(s))", app.trash.len());
+    let symbol = app.config.theme.icons.highlight_symbol.clone();
+    render_entries(
+        frame,
+        area,
+        title,
+        items,
+        highlight,
+        &symbol,
+        &mut app.trash_list_state,
+    );
+}
+
+fn render_entries(
+    frame: &mut Frame,
+    area: Rect,
+    title: String,
+    items: Vec<ListItem>,
+    highlight: Style,
+    highlight_symbol: &str,
+    state: &mut ListState,
+) {
+    let block = Block::bordered()
+        .title(title)
+        .title_alignment(Alignment::Center)
+        .border_type(BorderType::Rounded);
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(highlight)
+        .highlight_symbol(highlight_symbol);
+    frame.render_stateful_widget(list, area, state);
+}
+
+/// A row naming the task, where it came from and when it was removed.
+fn entry_item(
+    task: &Task,
+    location: &Location,
+    verb: &str,
+    removed_at: DateTime<Local>,
+) -> ListItem<'static> {
+    let mut spans = task_spans(task);
+    let subtask_count = task.descendant_count();
+    if subtask_count > 0 {
+        spans.push(Span::raw(format!(" (+{} subtask(s))", subtask_count)));
+    }
+    spans.push(Span::styled(
+        format!(
+            "  from {}, {} {}",
+            location.describe(),
+            verb,
+            removed_at.format("%Y-%m-%d")
+        ),
+        Style::default().add_modifier(Modifier::DIM),
+    ));
+    ListItem::new(Line::from(spans))
+}
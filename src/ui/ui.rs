@@ -1,10 +1,16 @@
-use crate::app::{state::AppMode, App};
+use crate::app::{state::AppMode, timer::format_duration, App};
 use crate::config::LayoutDirection;
-use crate::ui::utils::{format_key_event, parse_modifier};
+use crate::ui::backups::render_backups;
+use crate::ui::conflicts::render_conflicts;
+use crate::ui::details::render_details;
+use crate::ui::history::render_history;
+use crate::ui::removed::{render_archive, render_trash};
+use crate::ui::utils::{centered_rect, decoration_spans, format_key_event, parse_modifier};
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Style, Stylize},
-    widgets::{Block, BorderType, List, ListItem, Paragraph},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, Gauge, List, ListItem, Paragraph, Wrap},
     Frame,
 };
 use std::str::FromStr;
@@ -41,19 +47,57 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
     let theme = &app.config.theme;
     let main_fg = Color::from_str(&theme.colors.main_fg).unwrap_or(Color::White);
     let input_fg = Color::from_str(&theme.colors.input_fg).unwrap_or(Color::Yellow);
+    let marked_fg = Color::from_str(&theme.colors.marked_fg).unwrap_or(Color::Cyan);
     let highlight_mod = parse_modifier(&theme.other.highlight_mod);
     let highlight_symbol = &theme.icons.highlight_symbol; // Get the highlight symbol
 
     // Main task list
+    let marked = app.marked_ids();
+    let mut list_title = "Tasks".to_string();
+    for ancestor in app.breadcrumb() {
+        list_title.push_str(&format!(" › {}", ancestor));
+    }
+    if app.actionable_only {
+        list_title.push_str(" – next actions");
+    } else if let Some(group) = app.group {
+        list_title.push_str(&format!(" grouped by {:?}", group));
+    }
+    if let Some(sort) = app.sort {
+        list_title.push_str(&format!(" sorted by {:?}", sort));
+    }
+    if !marked.is_empty() {
+        list_title.push_str(&format!(" ({} marked)", marked.len()));
+    }
     let main_block = Block::bordered()
-        .title("Tasks")
+        .title(list_title)
         .title_alignment(Alignment::Center)
         .border_type(BorderType::Rounded);
 
     let tasks_to_display = app.get_tasks_to_display();
     let tasks: Vec<ListItem> = tasks_to_display
         .iter()
-        .map(|(display_name, _)| ListItem::new(display_name.as_str().to_string()))
+        .map(|(display_name, id)| {
+            let mut style = Style::default();
+            if id.is_none() {
+                // Group header
+                style = style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+            }
+            if id
+                .and_then(|id| app.task(id))
+                .is_some_and(|task| task.completed)
+            {
+                style = style.add_modifier(Modifier::CROSSED_OUT | Modifier::DIM);
+            }
+            if id.is_some_and(|id| marked.contains(&id)) {
+                style = style.fg(marked_fg).add_modifier(Modifier::BOLD);
+            }
+            // Decorations are drawn apart from the name so they cannot be mistaken for part of it
+            let mut spans = vec![Span::raw(display_name.clone())];
+            if let Some(task) = id.and_then(|id| app.task(id)) {
+                spans.extend(decoration_spans(task));
+            }
+            ListItem::new(Line::from(spans)).style(style)
+        })
         .collect();
 
     let task_list = List::new(tasks)
@@ -62,11 +106,69 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
         .highlight_style(Style::default().add_modifier(highlight_mod)) // Use themed modifier
         .highlight_symbol(highlight_symbol);
 
-    frame.render_stateful_widget(task_list, chunks[0], &mut app.task_list_state);
+    // The Pomodoro countdown sits below the list while a session runs
+    let list_area = match &app.pomodoro {
+        Some(pomodoro) => {
+            let [list_area, gauge_area] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(3)]).areas(chunks[0]);
+            let task_name = app
+                .task(pomodoro.task)
+                .map_or("", |task| task.name.as_str());
+            let gauge = Gauge::default()
+                .block(
+                    Block::bordered()
+                        .title(format!("{} – {}", pomodoro.phase.as_str(), task_name))
+                        .title_alignment(Alignment::Center)
+                        .border_type(BorderType::Rounded),
+                )
+                .gauge_style(Style::default().fg(input_fg))
+                .ratio(pomodoro.progress())
+                .label(format_duration(pomodoro.remaining));
+            frame.render_widget(gauge, gauge_area);
+            list_area
+        }
+        None => chunks[0],
+    };
+
+    // The detail pane takes the right side of the list area when shown
+    let list_area = if app.show_details {
+        let [list_area, details_area] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(list_area);
+        render_details(frame, details_area, app);
+        list_area
+    } else {
+        list_area
+    };
+    let browser_highlight = Style::default().add_modifier(highlight_mod);
+    match app.mode {
+        AppMode::Archive => render_archive(frame, list_area, app, browser_highlight),
+        AppMode::Trash => render_trash(frame, list_area, app, browser_highlight),
+        AppMode::Backups => render_backups(frame, list_area, app, browser_highlight),
+        AppMode::History => render_history(frame, list_area, app, browser_highlight),
+        AppMode::Conflicts => render_conflicts(frame, list_area, app, browser_highlight),
+        _ => frame.render_stateful_widget(task_list, list_area, &mut app.task_list_state),
+    }
 
     // Input/Editing block
     let help_text;
     let (input_title, mut input_style) = match app.mode {
+        AppMode::Tagging => (
+            "Tag Task (Press Enter to submit)",
+            Style::default().fg(input_fg),
+        ),
+        AppMode::ExpandDepth => (
+            "Levels to Show (Press Enter to submit)",
+            Style::default().fg(input_fg),
+        ),
+        AppMode::Visual { .. } => {
+            help_text = format!(
+                "Visual: move to extend, '{}' to mark range, '{}' to cancel",
+                format_key_event(app.config.keys.visual_mode),
+                format_key_event(app.config.keys.clear_marks),
+            );
+            (help_text.as_str(), Style::default().fg(marked_fg))
+        }
         AppMode::Editing => (
             "Add Task (Press Enter to submit)",
             Style::default().fg(input_fg), // Use themed color
@@ -75,10 +177,53 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
             "Edit Task (Press Enter to submit)",
             Style::default().fg(input_fg), // Use themed color
         ),
+        AppMode::Confirm { .. } => ("Confirm (y/n)", Style::default().fg(input_fg)),
+        AppMode::Backups => {
+            help_text = format!(
+                "Backups: '{}' to restore the selected backup, '{}' to go back",
+                format_key_event(app.config.keys.restore_task),
+                format_key_event(app.config.keys.show_backups),
+            );
+            (help_text.as_str(), Style::default().fg(input_fg))
+        }
+        AppMode::Conflicts => {
+            help_text = format!(
+                "Conflicts: '{}' to keep this side, '{}' to take theirs, '{}' to leave the rest as merged",
+                format_key_event(app.config.keys.keep_ours),
+                format_key_event(app.config.keys.keep_theirs),
+                format_key_event(app.config.keys.clear_marks),
+            );
+            (help_text.as_str(), Style::default().fg(input_fg))
+        }
+        AppMode::History => {
+            help_text = format!(
+                "History: '{}' to roll back to the selected commit, '{}' to go back",
+                format_key_event(app.config.keys.restore_task),
+                format_key_event(app.config.keys.show_history),
+            );
+            (help_text.as_str(), Style::default().fg(input_fg))
+        }
+        AppMode::Trash => {
+            help_text = format!(
+                "Trash: '{}' to restore, '{}' to delete permanently, '{}' to go back",
+                format_key_event(app.config.keys.restore_task),
+                format_key_event(app.config.keys.delete_task),
+                format_key_event(app.config.keys.show_trash),
+            );
+            (help_text.as_str(), Style::default().fg(input_fg))
+        }
+        AppMode::Archive => {
+            help_text = format!(
+                "Archive: '{}' to restore, '{}' to go back",
+                format_key_event(app.config.keys.restore_task),
+                format_key_event(app.config.keys.show_archive),
+            );
+            (help_text.as_str(), Style::default().fg(input_fg))
+        }
         AppMode::Normal => {
             let keybindings = &app.config.keys;
             help_text = format!(
-                "Press '{}' to add, '{}' to deselect, '{}' to edit, '{} to delete', '{}/{}' to navigate, {} to expand, '{}' to quit",
+                "Press '{}' to add, '{}' to deselect, '{}' to edit, '{} to delete', '{}/{}' to navigate, {} to expand, '{}' to complete, '{}' to mark, '{}' for visual, '{}/{}' to zoom, '{}' for details, '{}' to quit",
                 format_key_event(keybindings.add_task),
                 format_key_event(keybindings.deselect),
                 format_key_event(keybindings.edit_task),
@@ -86,6 +231,12 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
                 format_key_event(keybindings.select_previous),
                 format_key_event(keybindings.select_next),
                 format_key_event(keybindings.toggle_expand),
+                format_key_event(keybindings.toggle_complete),
+                format_key_event(keybindings.toggle_mark),
+                format_key_event(keybindings.visual_mode),
+                format_key_event(keybindings.zoom_in),
+                format_key_event(keybindings.zoom_out),
+                format_key_event(keybindings.toggle_details),
                 format_key_event(keybindings.quit),
             );
             (help_text.as_str(), Style::default())
@@ -97,19 +248,68 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
         input_style = input_style.fg(main_fg);
     }
 
-    let input_block = Block::bordered()
+    let mut input_block = Block::bordered()
         .title(input_title)
         .title_alignment(Alignment::Center)
         .border_type(BorderType::Rounded);
+    // The running timer stays in the corner of the status area whatever the mode
+    if let Some(timer) = &app.timer {
+        if let Some(task) = app.task(timer.task) {
+            let timer_text = format!(
+                " {}{} {} ",
+                app.config.theme.icons.timer,
+                task.name,
+                format_duration(timer.elapsed)
+            );
+            input_block = input_block.title(Line::from(timer_text).right_aligned());
+        }
+    }
 
-    let input_paragraph = Paragraph::new(app.input.as_str())
+    // Status feedback reuses the input box while nothing is being typed
+    let input_text = match (&app.mode, &app.status_message) {
+        (AppMode::Normal, Some(message)) => message.as_str(),
+        _ => app.input.as_str(),
+    };
+    let input_paragraph = Paragraph::new(input_text)
         .block(input_block)
         .style(input_style);
 
     frame.render_widget(input_paragraph, chunks[1]);
 
     // Set cursor position only when in an editing mode
-    if let AppMode::Editing | AppMode::EditingTask { .. } = app.mode {
+    if let AppMode::Editing
+    | AppMode::EditingTask { .. }
+    | AppMode::Tagging
+    | AppMode::ExpandDepth = app.mode
+    {
         frame.set_cursor_position((chunks[1].x + app.input.len() as u16 + 1, chunks[1].y + 1));
     }
+
+    // Confirmation popup, drawn last so it sits on top of everything else
+    if let AppMode::Confirm { message, .. } = &app.mode {
+        let popup_area = centered_rect(frame.area(), 50, 5);
+        let popup_block = Block::bordered()
+            .title("Confirm")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded)
+            .style(Style::default().fg(input_fg));
+        let popup = Paragraph::new(format!("{}\n[y]es / [n]o", message))
+            .block(popup_block)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+        frame.render_widget(Clear, popup_area);
+        frame.render_widget(popup, popup_area);
+    }
+
+    // Flash the whole screen when a Pomodoro phase ends
+    if app
+        .pomodoro
+        .as_ref()
+        .is_some_and(|pomodoro| pomodoro.flash_ticks > 0)
+    {
+        let area = frame.area();
+        frame
+            .buffer_mut()
+            .set_style(area, Style::default().add_modifier(Modifier::REVERSED));
+    }
 }
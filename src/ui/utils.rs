@@ -1,5 +1,8 @@
+use crate::app::task::Task;
 use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use ratatui::style::Modifier;
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Span;
 
 pub fn parse_modifier(modifier_str: &str) -> Modifier {
     match modifier_str.to_lowercase().as_str() {
@@ -34,3 +37,41 @@ pub fn format_key_event(key_event: KeyEvent) -> String {
     }
     s
 }
+
+/// Returns a rect of `percent_x` width and `height` rows centered inside `area`.
+pub fn centered_rect(area: Rect, percent_x: u16, height: u16) -> Rect {
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::horizontal([Constraint::Percentage(percent_x)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
+}
+
+/// The task's name followed by its decorations.
+pub fn task_spans(task: &Task) -> Vec<Span<'static>> {
+    let mut spans = vec![Span::raw(task.name.clone())];
+    spans.extend(decoration_spans(task));
+    spans
+}
+
+/// The task's tags, priority, due date and recurrence, each after a space and dimmed so they
+/// read apart from the name.
+pub fn decoration_spans(task: &Task) -> Vec<Span<'static>> {
+    let mut decorations: Vec<String> = task.tags.iter().map(|tag| format!("#{}", tag)).collect();
+    if let Some(priority) = task.priority {
+        decorations.push(format!("!{}", priority.as_str()));
+    }
+    if let Some(due) = task.due {
+        decorations.push(format!("due {}", due));
+    }
+    if let Some(recurrence) = &task.recurrence {
+        decorations.push(recurrence.describe());
+    }
+    let dim = Style::default().add_modifier(Modifier::DIM);
+    decorations
+        .into_iter()
+        .map(|decoration| Span::styled(format!(" {}", decoration), dim))
+        .collect()
+}